@@ -0,0 +1,24 @@
+use collate::{CollationElementTable, Collator};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn long_ascii(c: &mut Criterion) {
+    let table = CollationElementTable::default();
+    let s = "The quick brown fox jumps over the lazy dog. ".repeat(100);
+
+    c.bench_function("sort key of long ASCII string", |b| {
+        b.iter(|| table.generate_sort_key(black_box(&s)))
+    });
+    // The general path, which normalizes every character
+    c.bench_function("sort key of long ASCII string from chars", |b| {
+        b.iter(|| table.generate_sort_key_from_chars(black_box(&s).chars()))
+    });
+
+    let collator = Collator::default();
+    let t = format!("{}!", s);
+    c.bench_function("compare long ASCII strings", |b| {
+        b.iter(|| collator.compare(black_box(&s), black_box(&t)))
+    });
+}
+
+criterion_group!(benches, long_ascii);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+use collate::CollationElementTable;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn non_ascii(c: &mut Criterion) {
+    let table = CollationElementTable::default();
+
+    let s = "Größenwahn führt zu Ärger, sagte Ångström über Æsir und Œuvres. ".repeat(50);
+    c.bench_function("sort key of accented Latin text", |b| {
+        b.iter(|| table.generate_sort_key(black_box(&s)))
+    });
+    let s = "Съешь же ещё этих мягких французских булок, да выпей чаю. ".repeat(50);
+    c.bench_function("sort key of Cyrillic text", |b| {
+        b.iter(|| table.generate_sort_key(black_box(&s)))
+    });
+    let s = "漢字かな交じり文は日本語の表記です。".repeat(50);
+    c.bench_function("sort key of Japanese text", |b| {
+        b.iter(|| table.generate_sort_key(black_box(&s)))
+    });
+}
+
+criterion_group!(benches, non_ascii);
+criterion_main!(benches);
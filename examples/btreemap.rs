@@ -0,0 +1,41 @@
+//! Counts the words of a text in a `BTreeMap` that iterates in collation
+//! order, and groups them by the letter of an alphabetical index.
+//!
+//! Run with `cargo run --example btreemap`.
+
+use collate::Collator;
+use std::collections::BTreeMap;
+
+const TEXT: &str = "Éléonore and Zoë ate an apple, an Apfel and an éclair \
+                    while Émile and zoe read about Zürich and Ångström.";
+
+fn main() {
+    let collator = Collator::default();
+
+    // Keys that carry their sort key keep the map in collation order
+    let mut counts = BTreeMap::new();
+    for word in TEXT.split(|c: char| !c.is_alphabetic()) {
+        if !word.is_empty() {
+            *counts.entry(collator.collated(word)).or_insert(0) += 1;
+        }
+    }
+    println!("Word counts:");
+    for (word, count) in &counts {
+        println!("  {:10} {}", word, count);
+    }
+
+    // The map is ordered, so each bucket is a run of consecutive words
+    let mut index: BTreeMap<_, Vec<&str>> = BTreeMap::new();
+    for word in counts.keys() {
+        if let Some(label) = collator.first_primary_label(word.as_str()) {
+            index
+                .entry(collator.collated(label.to_string()))
+                .or_default()
+                .push(word.as_str());
+        }
+    }
+    println!("Index:");
+    for (label, words) in &index {
+        println!("  {}: {}", label, words.join(", "));
+    }
+}
@@ -0,0 +1,39 @@
+//! Searches a text and a sorted list while ignoring case and accents, by
+//! comparing at the primary strength.
+//!
+//! Run with `cargo run --example search -- [query]`.
+
+use collate::{Collator, Strength};
+use std::{cmp::Ordering, env};
+
+const TEXT: &str = "Le café de la Résidence sert un CAFE crème, \
+                    et le cafe du coin un café noir.";
+
+fn main() {
+    let query = env::args().nth(1).unwrap_or_else(|| "cafe".into());
+    let collator = Collator::default();
+    let primary = collator.with_strength(Strength::Primary);
+
+    // Every word that only differs from the query in case or accents
+    println!("Matches of {:?}:", query);
+    let mut offset = 0;
+    for word in TEXT.split(' ') {
+        let word_offset = offset;
+        offset += word.len() + 1;
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+        if primary.compare(word, &query) == Ordering::Equal {
+            println!("  {:?} at byte {}", word, word_offset);
+        }
+    }
+
+    // Binary search in a list sorted by the same collator finds the range
+    // of entries that are equal at the primary strength
+    let mut words: Vec<_> = TEXT
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    words.sort_by(collator.cmp_fn());
+    let start = words.partition_point(|w| primary.compare(w, &query) == Ordering::Less);
+    let end = words.partition_point(|w| primary.compare(w, &query) != Ordering::Greater);
+    println!("In the sorted list: {:?}", &words[start..end]);
+}
@@ -0,0 +1,46 @@
+//! Sorts a list of names with the root collation, with punctuation ignored
+//! and with the German phonebook collation.
+//!
+//! Run with `cargo run --example sort_names`.
+
+use collate::{Collator, VariableWeighting};
+
+fn print(title: &str, names: &[&str]) {
+    println!("{}:", title);
+    for name in names {
+        println!("  {}", name);
+    }
+}
+
+fn main() {
+    let mut names = vec![
+        "Müller",
+        "Mueller",
+        "Muller",
+        "Mahler",
+        "de Vries",
+        "Dvořák",
+        "Ångström",
+        "Adams",
+        "O'Brien",
+        "Obama",
+        "Ochoa",
+        "van Dijk",
+        "Vance",
+    ];
+
+    let mut collator = Collator::default();
+    names.sort_by(collator.cmp_fn());
+    print("Root collation", &names);
+
+    // Spaces and punctuation only break ties
+    collator.set_variable_weighting(VariableWeighting::Shifted);
+    names.sort_by(collator.cmp_fn());
+    print("Ignoring punctuation", &names);
+
+    // In German phonebooks, `ü` sorts like `ue`. Sorting by cached keys
+    // generates each key only once, instead of twice for every comparison.
+    let collator = Collator::for_locale("de-DE-u-co-phonebk").unwrap();
+    names.sort_by_cached_key(|name| collator.sort_key(name));
+    print("German phonebook", &names);
+}
@@ -0,0 +1,268 @@
+//! Sorting ASCII text without the collation element table.
+
+use std::cmp::Ordering;
+
+// The primary, secondary and tertiary weights of every ASCII character in the
+// bundled table, by code point, which have to be updated with the table.
+// Control characters other than whitespace are completely ignorable.
+const WEIGHTS: [(u16, u16, u16); 128] = [
+    (0x0000, 0x0000, 0x0000), // NUL
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0201, 0x0020, 0x0002), // tab
+    (0x0202, 0x0020, 0x0002), // line feed
+    (0x0203, 0x0020, 0x0002), // vertical tab
+    (0x0204, 0x0020, 0x0002), // form feed
+    (0x0205, 0x0020, 0x0002), // carriage return
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0000, 0x0000, 0x0000), // control
+    (0x0209, 0x0020, 0x0002), // space
+    (0x0267, 0x0020, 0x0002), // !
+    (0x031D, 0x0020, 0x0002), // "
+    (0x03AC, 0x0020, 0x0002), // #
+    (0x1F64, 0x0020, 0x0002), // $
+    (0x03AD, 0x0020, 0x0002), // %
+    (0x03A9, 0x0020, 0x0002), // &
+    (0x0316, 0x0020, 0x0002), // '
+    (0x0328, 0x0020, 0x0002), // (
+    (0x0329, 0x0020, 0x0002), // )
+    (0x03A1, 0x0020, 0x0002), // *
+    (0x0666, 0x0020, 0x0002), // +
+    (0x0223, 0x0020, 0x0002), // ,
+    (0x020D, 0x0020, 0x0002), // -
+    (0x027E, 0x0020, 0x0002), // .
+    (0x03A6, 0x0020, 0x0002), // /
+    (0x1F98, 0x0020, 0x0002), // 0
+    (0x1F99, 0x0020, 0x0002), // 1
+    (0x1F9A, 0x0020, 0x0002), // 2
+    (0x1F9B, 0x0020, 0x0002), // 3
+    (0x1F9C, 0x0020, 0x0002), // 4
+    (0x1F9D, 0x0020, 0x0002), // 5
+    (0x1F9E, 0x0020, 0x0002), // 6
+    (0x1F9F, 0x0020, 0x0002), // 7
+    (0x1FA0, 0x0020, 0x0002), // 8
+    (0x1FA1, 0x0020, 0x0002), // 9
+    (0x0240, 0x0020, 0x0002), // :
+    (0x023A, 0x0020, 0x0002), // ;
+    (0x066A, 0x0020, 0x0002), // <
+    (0x066B, 0x0020, 0x0002), // =
+    (0x066C, 0x0020, 0x0002), // >
+    (0x026D, 0x0020, 0x0002), // ?
+    (0x03A0, 0x0020, 0x0002), // @
+    (0x1FA2, 0x0020, 0x0008), // A
+    (0x1FBC, 0x0020, 0x0008), // B
+    (0x1FD6, 0x0020, 0x0008), // C
+    (0x1FEB, 0x0020, 0x0008), // D
+    (0x2007, 0x0020, 0x0008), // E
+    (0x2042, 0x0020, 0x0008), // F
+    (0x2051, 0x0020, 0x0008), // G
+    (0x2075, 0x0020, 0x0008), // H
+    (0x2090, 0x0020, 0x0008), // I
+    (0x20AB, 0x0020, 0x0008), // J
+    (0x20C4, 0x0020, 0x0008), // K
+    (0x20D6, 0x0020, 0x0008), // L
+    (0x2109, 0x0020, 0x0008), // M
+    (0x2118, 0x0020, 0x0008), // N
+    (0x213C, 0x0020, 0x0008), // O
+    (0x216B, 0x0020, 0x0008), // P
+    (0x2180, 0x0020, 0x0008), // Q
+    (0x2193, 0x0020, 0x0008), // R
+    (0x21D2, 0x0020, 0x0008), // S
+    (0x21F7, 0x0020, 0x0008), // T
+    (0x2217, 0x0020, 0x0008), // U
+    (0x2247, 0x0020, 0x0008), // V
+    (0x2259, 0x0020, 0x0008), // W
+    (0x2264, 0x0020, 0x0008), // X
+    (0x2270, 0x0020, 0x0008), // Y
+    (0x2286, 0x0020, 0x0008), // Z
+    (0x032A, 0x0020, 0x0002), // [
+    (0x03A7, 0x0020, 0x0002), // \
+    (0x032B, 0x0020, 0x0002), // ]
+    (0x04B7, 0x0020, 0x0002), // ^
+    (0x020B, 0x0020, 0x0002), // _
+    (0x04B4, 0x0020, 0x0002), // `
+    (0x1FA2, 0x0020, 0x0002), // a
+    (0x1FBC, 0x0020, 0x0002), // b
+    (0x1FD6, 0x0020, 0x0002), // c
+    (0x1FEB, 0x0020, 0x0002), // d
+    (0x2007, 0x0020, 0x0002), // e
+    (0x2042, 0x0020, 0x0002), // f
+    (0x2051, 0x0020, 0x0002), // g
+    (0x2075, 0x0020, 0x0002), // h
+    (0x2090, 0x0020, 0x0002), // i
+    (0x20AB, 0x0020, 0x0002), // j
+    (0x20C4, 0x0020, 0x0002), // k
+    (0x20D6, 0x0020, 0x0002), // l
+    (0x2109, 0x0020, 0x0002), // m
+    (0x2118, 0x0020, 0x0002), // n
+    (0x213C, 0x0020, 0x0002), // o
+    (0x216B, 0x0020, 0x0002), // p
+    (0x2180, 0x0020, 0x0002), // q
+    (0x2193, 0x0020, 0x0002), // r
+    (0x21D2, 0x0020, 0x0002), // s
+    (0x21F7, 0x0020, 0x0002), // t
+    (0x2217, 0x0020, 0x0002), // u
+    (0x2247, 0x0020, 0x0002), // v
+    (0x2259, 0x0020, 0x0002), // w
+    (0x2264, 0x0020, 0x0002), // x
+    (0x2270, 0x0020, 0x0002), // y
+    (0x2286, 0x0020, 0x0002), // z
+    (0x032C, 0x0020, 0x0002), // {
+    (0x066E, 0x0020, 0x0002), // |
+    (0x032D, 0x0020, 0x0002), // }
+    (0x0670, 0x0020, 0x0002), // ~
+    (0x0000, 0x0000, 0x0000), // DEL
+];
+
+/// A collator for ASCII text that sorts like the root [`Collator`] with its
+/// default settings, but with the weights of ASCII compiled in, so no
+/// collation element table has to be loaded or even bundled. Characters
+/// outside ASCII sort after all ASCII characters, by code point.
+///
+/// ```
+/// use collate::AsciiCollator;
+///
+/// let mut words = ["banana", "Apple", "apple", "cherry"];
+/// AsciiCollator.sort(&mut words);
+/// assert_eq!(words, ["apple", "Apple", "banana", "cherry"]);
+/// ```
+///
+/// [`Collator`]: crate::Collator
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AsciiCollator;
+
+impl AsciiCollator {
+    /// Compares `a` and `b`.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        (0..3)
+            .map(|level| level_weights(a, level).cmp(level_weights(b, level)))
+            .find(|&ordering| ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// Sorts `items` with [`AsciiCollator::compare`].
+    pub fn sort<T: AsRef<str>>(&self, items: &mut [T]) {
+        items.sort_by(|a, b| self.compare(a.as_ref(), b.as_ref()));
+    }
+}
+
+// The weights of `s` at a level, without the ignorable ones
+fn level_weights(s: &str, level: usize) -> impl Iterator<Item = u32> + '_ {
+    s.chars()
+        .map(move |c| {
+            let (primary, secondary, tertiary) = match WEIGHTS.get(c as usize) {
+                Some(&(p, s, t)) => (p as u32, s, t),
+                None => (0x10000 + c as u32, 0x0020, 0x0002),
+            };
+            [primary, secondary as u32, tertiary as u32][level]
+        })
+        .filter(|&weight| weight != 0)
+}
+
+#[cfg(all(test, feature = "bundled-ducet"))]
+mod tests {
+    use super::*;
+    use crate::{CollationElementTable, Collator};
+
+    #[test]
+    fn matches_root() {
+        let root = Collator::root();
+        let chars: Vec<String> = (0..128u8).map(|b| char::from(b).to_string()).collect();
+        let mut strings: Vec<String> = chars.clone();
+        for a in &chars {
+            for b in ["a", "A", "-", "1", "\u{1}"] {
+                strings.push(format!("{}{}", a, b));
+                strings.push(format!("{}{}", b, a));
+            }
+        }
+        strings.extend(
+            [
+                "hello world",
+                "Hello World",
+                "hello-world",
+                "hello_world",
+                "helloworld",
+                "co-op",
+                "coop",
+                "Coop",
+                "CO-OP",
+                "file10.txt",
+                "file9.txt",
+                "FILE9.TXT",
+                "a\tb",
+                "a b",
+                "ab\u{0}",
+                "",
+                " ",
+            ]
+            .iter()
+            .map(|s| s.to_string()),
+        );
+
+        for a in &strings {
+            for b in strings.iter().step_by(7) {
+                assert_eq!(
+                    AsciiCollator.compare(a, b),
+                    root.compare(a, b),
+                    "{:?} and {:?}",
+                    a,
+                    b
+                );
+            }
+        }
+
+        let mut ascii = strings.clone();
+        let mut expected = strings;
+        AsciiCollator.sort(&mut ascii);
+        root.sort_by(&mut expected, |s| s);
+        assert_eq!(ascii, expected);
+    }
+
+    #[test]
+    fn weights_of_table() {
+        let table = CollationElementTable::default();
+        for (c, &(primary, secondary, tertiary)) in WEIGHTS.iter().enumerate() {
+            let c = char::from(c as u8);
+            let elements = &table[c.encode_utf8(&mut [0; 4])];
+            assert_eq!(elements.len(), 1, "{:?}", c);
+            assert_eq!(
+                (
+                    elements[0].primary,
+                    elements[0].secondary,
+                    elements[0].tertiary
+                ),
+                (primary, secondary, tertiary),
+                "{:?}",
+                c
+            );
+        }
+    }
+
+    #[test]
+    fn non_ascii_last() {
+        assert_eq!(AsciiCollator.compare("z", "\u{e9}"), Ordering::Less);
+        assert_eq!(AsciiCollator.compare("\u{e9}", "\u{e8}"), Ordering::Greater);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::SortKey;
+use std::collections::{BTreeMap, HashMap};
+
+/// A least recently used cache of sort keys, keyed by the input string.
+pub(crate) struct KeyCache {
+    capacity: usize,
+    // Incremented on every access to track how recently entries were used
+    clock: u64,
+    entries: HashMap<String, (u64, SortKey)>,
+    by_last_use: BTreeMap<u64, String>,
+    #[cfg(test)]
+    pub(crate) hits: usize,
+}
+
+impl KeyCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            clock: 0,
+            entries: HashMap::new(),
+            by_last_use: BTreeMap::new(),
+            #[cfg(test)]
+            hits: 0,
+        }
+    }
+
+    pub(crate) fn get(&mut self, s: &str) -> Option<SortKey> {
+        let (last_use, key) = self.entries.get_mut(s)?;
+        self.clock += 1;
+        let s = self.by_last_use.remove(last_use)?;
+        self.by_last_use.insert(self.clock, s);
+        *last_use = self.clock;
+        #[cfg(test)]
+        {
+            self.hits += 1;
+        }
+        Some(key.clone())
+    }
+
+    pub(crate) fn insert(&mut self, s: &str, key: SortKey) {
+        // Another thread may have inserted the same key in the meantime
+        if self.capacity == 0 || self.entries.contains_key(s) {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = self.by_last_use.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.by_last_use.insert(self.clock, s.to_owned());
+        self.entries.insert(s.to_owned(), (self.clock, key));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.by_last_use.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = KeyCache::new(2);
+        cache.insert("a", SortKey::new());
+        cache.insert("b", SortKey::new());
+        assert!(cache.get("a").is_some());
+
+        cache.insert("c", SortKey::new());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+        assert_eq!(cache.hits, 3);
+
+        cache.clear();
+        assert!(cache.get("a").is_none());
+    }
+}
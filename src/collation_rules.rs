@@ -10,8 +10,8 @@ use nom::{
         },
         is_hex_digit,
     },
-    combinator::{all_consuming, map, map_opt, opt, recognize, value},
-    multi::{count, many0, many1, many_m_n, separated_list0},
+    combinator::{all_consuming, eof, map, map_opt, opt, recognize, value, verify},
+    multi::{count, many0, many1, many_m_n, separated_list1},
     sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
     IResult,
 };
@@ -28,13 +28,31 @@ pub struct CollationRules {
     pub(crate) rules: Vec<Rule>,
 }
 
+impl CollationRules {
+    /// Layers `other` on top of these rules, like user rules on top of those
+    /// of a locale. The rules of `other` are applied after these, so their
+    /// resets can refer to the characters that these rules placed, and their
+    /// settings override these.
+    pub fn then(mut self, other: CollationRules) -> CollationRules {
+        self.settings.extend(other.settings);
+        self.rules.extend(other.rules);
+        self
+    }
+}
+
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum Rule {
     SetContext {
         before: Option<u8>,
         sequence: String,
     },
+    SetPosition {
+        before: Option<u8>,
+        position: Position,
+    },
     Equal {
+        prefix: Option<String>,
+        extension: Option<String>,
         sequence: String,
     },
     MultiEqual {
@@ -52,13 +70,73 @@ pub enum Rule {
     },
 }
 
+/// A logical position in the table that a reset can refer to instead of a
+/// sequence, like `&[last regular]`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Position {
+    FirstTertiaryIgnorable,
+    LastTertiaryIgnorable,
+    FirstSecondaryIgnorable,
+    LastSecondaryIgnorable,
+    FirstPrimaryIgnorable,
+    LastPrimaryIgnorable,
+    FirstVariable,
+    LastVariable,
+    FirstRegular,
+    LastRegular,
+    FirstImplicit,
+    FirstTrailing,
+    LastTrailing,
+}
+
+impl Position {
+    const ALL: [Position; 13] = [
+        Position::FirstTertiaryIgnorable,
+        Position::LastTertiaryIgnorable,
+        Position::FirstSecondaryIgnorable,
+        Position::LastSecondaryIgnorable,
+        Position::FirstPrimaryIgnorable,
+        Position::LastPrimaryIgnorable,
+        Position::FirstVariable,
+        Position::LastVariable,
+        Position::FirstRegular,
+        Position::LastRegular,
+        Position::FirstImplicit,
+        Position::FirstTrailing,
+        Position::LastTrailing,
+    ];
+
+    /// The name of the position in rules, without the brackets.
+    pub fn name(self) -> &'static str {
+        match self {
+            Position::FirstTertiaryIgnorable => "first tertiary ignorable",
+            Position::LastTertiaryIgnorable => "last tertiary ignorable",
+            Position::FirstSecondaryIgnorable => "first secondary ignorable",
+            Position::LastSecondaryIgnorable => "last secondary ignorable",
+            Position::FirstPrimaryIgnorable => "first primary ignorable",
+            Position::LastPrimaryIgnorable => "last primary ignorable",
+            Position::FirstVariable => "first variable",
+            Position::LastVariable => "last variable",
+            Position::FirstRegular => "first regular",
+            Position::LastRegular => "last regular",
+            Position::FirstImplicit => "first implicit",
+            Position::FirstTrailing => "first trailing",
+            Position::LastTrailing => "last trailing",
+        }
+    }
+}
+
+/// An element of the list after `<*` or `=*`. Like in CLDR, such a list is a
+/// list of single code points, so a contraction can't be an element and has
+/// to be tailored with its own relation instead. Quoted strings in the list
+/// are split into their characters as well.
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum SequenceElement {
     Range(RangeInclusive<char>),
     Char(char),
 }
 
-pub fn cldr<'a>(i: &'a str) -> Result<CollationRules, nom::Err<nom::error::Error<&'a str>>> {
+pub fn cldr(i: &str) -> Result<CollationRules, nom::Err<nom::error::Error<&str>>> {
     match map(
         all_consuming(delimited(
             comment,
@@ -74,22 +152,40 @@ pub fn cldr<'a>(i: &'a str) -> Result<CollationRules, nom::Err<nom::error::Error
 }
 
 fn settings(i: &str) -> IResult<&str, Vec<(String, String)>> {
-    separated_list0(comment, setting)(i)
+    many0(terminated(setting, comment))(i)
 }
 
-// [key value]
+// [key value], where the value can be a list like `[reorder Grek Latn]` or a
+// set like `[suppressContractions [Љ-ґ]]`
 fn setting(i: &str) -> IResult<&str, (String, String)> {
     delimited(
         char('['),
         separated_pair(
             map(identifier, |s| s.into()),
             space1,
-            map(identifier, |s| s.into()),
+            map(
+                alt((unicode_set, recognize(separated_list1(space1, identifier)))),
+                |s| s.into(),
+            ),
         ),
         char(']'),
     )(i)
 }
 
+// A set of characters like `[a-z[:Greek:]]`, which is only recognized because
+// the settings that take a set don't change the order
+fn unicode_set(i: &str) -> IResult<&str, &str> {
+    recognize(delimited(
+        char('['),
+        many0(alt((
+            value((), none_of(r"[]\")),
+            value((), pair(char('\\'), anychar)),
+            value((), unicode_set),
+        ))),
+        char(']'),
+    ))(i)
+}
+
 fn identifier(i: &str) -> IResult<&str, &str> {
     recognize(many1(alt((alphanumeric1, tag("-")))))(i)
 }
@@ -121,12 +217,10 @@ fn multi_increment(i: &str) -> IResult<&str, Rule> {
 }
 
 fn increment(i: &str) -> IResult<&str, Rule> {
-    let (i, (level, sequence, prefix, extension)) = tuple((
+    let (i, (level, (prefix, sequence, extension))) = pair(
         map(many_m_n(1, 4, char('<')), |s| s.len() as u8),
-        preceded(comment, sequence),
-        opt(preceded(tuple((comment, char('|'), comment)), sequence)),
-        opt(preceded(tuple((comment, char('/'), comment)), sequence)),
-    ))(i)?;
+        preceded(comment, relation_target),
+    )(i)?;
     Ok((
         i,
         Rule::Increment {
@@ -138,13 +232,45 @@ fn increment(i: &str) -> IResult<&str, Rule> {
     ))
 }
 
+// The sequence of a relation with its optional prefix and extension, like
+// `ab | cd / ef`
+fn relation_target(i: &str) -> IResult<&str, (Option<String>, String, Option<String>)> {
+    let (i, (first, second, extension)) = tuple((
+        sequence,
+        opt(preceded(tuple((comment, char('|'), comment)), sequence)),
+        opt(preceded(tuple((comment, char('/'), comment)), sequence)),
+    ))(i)?;
+    // In `prefix | sequence`, the prefix comes first
+    Ok(match second {
+        Some(second) => (i, (Some(first), second, extension)),
+        None => (i, (None, first, extension)),
+    })
+}
+
 fn set_context(i: &str) -> IResult<&str, Rule> {
-    map(
-        preceded(
-            pair(char('&'), comment),
-            pair(opt(terminated(before, comment)), sequence),
-        ),
-        |(before, sequence)| Rule::SetContext { before, sequence },
+    preceded(
+        pair(char('&'), comment),
+        alt((
+            map(
+                pair(opt(terminated(before, comment)), position),
+                |(before, position)| Rule::SetPosition { before, position },
+            ),
+            map(
+                pair(opt(terminated(before, comment)), sequence),
+                |(before, sequence)| Rule::SetContext { before, sequence },
+            ),
+        )),
+    )(i)
+}
+
+// [last regular]
+fn position(i: &str) -> IResult<&str, Position> {
+    delimited(
+        char('['),
+        map_opt(recognize(many1(none_of("]"))), |name| {
+            Position::ALL.iter().copied().find(|p| p.name() == name)
+        }),
+        char(']'),
     )(i)
 }
 
@@ -167,31 +293,56 @@ fn multi_equal(i: &str) -> IResult<&str, Rule> {
 }
 
 fn equal(i: &str) -> IResult<&str, Rule> {
-    map(preceded(pair(char('='), comment), sequence), |sequence| {
-        Rule::Equal { sequence }
-    })(i)
+    map(
+        preceded(pair(char('='), comment), relation_target),
+        |(prefix, sequence, extension)| Rule::Equal {
+            prefix,
+            extension,
+            sequence,
+        },
+    )(i)
 }
 
 fn is_reserved_char(c: char) -> bool {
     c.is_whitespace()
-        || (c >= '\u{0021}' && c <= '\u{002f}')
-        || (c >= '\u{003A}' && c <= '\u{0040}')
-        || (c >= '\u{005B}' && c <= '\u{0060}')
-        || (c >= '\u{007B}' && c <= '\u{007E}')
+        || ('\u{0021}'..='\u{002f}').contains(&c)
+        || ('\u{003A}'..='\u{0040}').contains(&c)
+        || ('\u{005B}'..='\u{0060}').contains(&c)
+        || ('\u{007B}'..='\u{007E}').contains(&c)
 }
 
 fn legal_char(i: &str) -> IResult<&str, char> {
     satisfy(|c| !is_reserved_char(c))(i)
 }
 
+// The end points of a range must be single characters in the right order,
+// so `c-a` and `'ab'-c` are rejected
 fn multisequence(i: &str) -> IResult<&str, Vec<SequenceElement>> {
-    many1(alt((
-        map(
-            separated_pair(legal_char, char('-'), legal_char),
-            |(beg, end)| SequenceElement::Range(beg..=end),
-        ),
-        map(legal_char, |c| SequenceElement::Char(c)),
-    )))(i)
+    map(
+        many1(alt((
+            map(
+                verify(
+                    separated_pair(single_char, char('-'), single_char),
+                    |(beg, end)| beg <= end,
+                ),
+                |(beg, end)| vec![SequenceElement::Range(beg..=end)],
+            ),
+            map(single_char, |c| vec![SequenceElement::Char(c)]),
+            map(quoted_chars, |s| {
+                s.chars().map(SequenceElement::Char).collect()
+            }),
+        ))),
+        |v| v.into_iter().flatten().collect(),
+    )(i)
+}
+
+// A legal character, an escaped one or a single quoted one, like `'\u0041'`
+fn single_char(i: &str) -> IResult<&str, char> {
+    alt((
+        legal_char,
+        escaped_char,
+        delimited(char('\''), alt((none_of(r"\'"), escaped_char)), char('\'')),
+    ))(i)
 }
 
 fn sequence(i: &str) -> IResult<&str, String> {
@@ -201,16 +352,19 @@ fn sequence(i: &str) -> IResult<&str, String> {
                 recognize(many1(satisfy(|c| !is_reserved_char(c)))),
                 |s: &str| s.to_owned(),
             ),
+            value("'".to_owned(), tag("''")),
             quoted_chars,
+            map(escaped_char, String::from),
         ))),
         |v| v.into_iter().collect(),
     )(i)
 }
 
+// Two apostrophes are a literal apostrophe, both inside and outside quotes
 fn quoted_chars(i: &str) -> IResult<&str, String> {
     delimited(
         char('\''),
-        many1(alt((none_of(r"\'"), escaped_char))),
+        many1(alt((none_of(r"\'"), value('\'', tag("''")), escaped_char))),
         char('\''),
     )(i)
     .map(|(i, v)| (i, v.iter().collect()))
@@ -250,20 +404,20 @@ fn hex_digits(n: u8) -> impl Fn(&str) -> IResult<&str, char> {
                 satisfy(|c| c < u8::MAX as char && is_hex_digit(c as u8)),
                 n as usize,
             )),
-            |out: &str| {
-                u32::from_str_radix(out, 16)
-                    .ok()
-                    .and_then(|u| char::from_u32(u))
-            },
+            |out: &str| u32::from_str_radix(out, 16).ok().and_then(char::from_u32),
         )(i)
     }
 }
 
-// Matches whitespace, optionally with a comment
+// Matches whitespace, optionally with a comment that ends the line or the
+// rules
 fn comment(i: &str) -> IResult<&str, ()> {
     delimited(
         multispace0,
-        value((), opt(tuple((char('#'), not_line_ending, line_ending)))),
+        value(
+            (),
+            opt(tuple((char('#'), not_line_ending, alt((line_ending, eof))))),
+        ),
         multispace0,
     )(i)
 }
@@ -271,6 +425,8 @@ fn comment(i: &str) -> IResult<&str, ()> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::CollationElementTable;
+    use proptest::prelude::*;
 
     #[test]
     fn test_escaped_char() {
@@ -299,6 +455,14 @@ mod test {
         );
 
         assert_eq!(sequence("hello world"), Ok((" world", "hello".into())));
+        assert_eq!(sequence("''"), Ok(("", "'".into())));
+        assert_eq!(sequence("'it''s'"), Ok(("", "it's".into())));
+        assert_eq!(sequence(r"'\''"), Ok(("", "'".into())));
+        // Escapes don't have to be quoted
+        assert_eq!(
+            sequence(r"\u00e6\u0301x y"),
+            Ok((" y", "\u{e6}\u{301}x".into()))
+        );
     }
 
     #[test]
@@ -357,6 +521,24 @@ mod test {
         )
     }
 
+    #[test]
+    fn test_star_lists() {
+        use SequenceElement::*;
+        assert_eq!(
+            multisequence(r"a'bc'd'\u0065'-g"),
+            Ok((
+                "",
+                vec![Char('a'), Char('b'), Char('c'), Char('d'), Range('e'..='g')]
+            ))
+        );
+        // Contractions can't be elements, a quoted string is a list of chars
+        assert_eq!(multisequence("'ch'"), Ok(("", vec![Char('c'), Char('h')])));
+        assert_eq!(multisequence("z-a"), Ok(("-a", vec![Char('z')])));
+        assert!(cldr("&a <* z-a").is_err());
+        assert!(cldr("&a <* 'ab'-c").is_err());
+        assert!(cldr("&a <* b|c").is_err());
+    }
+
     #[test]
     fn test_rules() {
         assert_eq!(
@@ -412,6 +594,8 @@ mod test {
                         sequence: "e".into(),
                     },
                     Rule::Equal {
+                        prefix: None,
+                        extension: None,
                         sequence: "f".into(),
                     }
                 ]
@@ -427,9 +611,9 @@ mod test {
                 "",
                 Rule::Increment {
                     level: 3,
-                    prefix: Some("cd".into()),
+                    prefix: Some("ab".into()),
                     extension: Some("ef".into()),
-                    sequence: "ab".into(),
+                    sequence: "cd".into(),
                 }
             )),
         );
@@ -440,9 +624,9 @@ mod test {
                 "",
                 Rule::Increment {
                     level: 3,
-                    prefix: Some("cd".into()),
+                    prefix: Some("ab".into()),
                     extension: Some("ef".into()),
-                    sequence: "ab".into(),
+                    sequence: "cd".into(),
                 }
             )),
         );
@@ -453,9 +637,9 @@ mod test {
                 "",
                 Rule::Increment {
                     level: 2,
-                    prefix: Some("cd".into()),
+                    prefix: Some("ab".into()),
                     extension: None,
-                    sequence: "ab".into(),
+                    sequence: "cd".into(),
                 }
             )),
         );
@@ -471,6 +655,18 @@ mod test {
                     sequence: "ab".into(),
                 }
             )),
+        );
+
+        assert_eq!(
+            rule("= ab | cd / ef"),
+            Ok((
+                "",
+                Rule::Equal {
+                    prefix: Some("ab".into()),
+                    extension: Some("ef".into()),
+                    sequence: "cd".into(),
+                }
+            )),
         )
     }
 
@@ -512,6 +708,68 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_position() {
+        assert_eq!(position("[last regular]"), Ok(("", Position::LastRegular)));
+        assert_eq!(
+            position("[first tertiary ignorable]"),
+            Ok(("", Position::FirstTertiaryIgnorable))
+        );
+        assert!(position("[last irregular]").is_err());
+
+        assert_eq!(
+            rule("&[last regular] < x"),
+            Ok((
+                " < x",
+                Rule::SetPosition {
+                    before: None,
+                    position: Position::LastRegular,
+                }
+            ))
+        );
+        assert_eq!(
+            rule("&[before 1][first variable]"),
+            Ok((
+                "",
+                Rule::SetPosition {
+                    before: Some(1),
+                    position: Position::FirstVariable,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_settings() {
+        assert_eq!(
+            settings("[alternate shifted]\n[reorder Grek others] &a"),
+            Ok((
+                "&a",
+                vec![
+                    ("alternate".into(), "shifted".into()),
+                    ("reorder".into(), "Grek others".into())
+                ]
+            ))
+        );
+        assert_eq!(
+            setting("[suppressContractions [\u{409}-\u{491}[:Cyrl:]]]"),
+            Ok((
+                "",
+                (
+                    "suppressContractions".into(),
+                    "[\u{409}-\u{491}[:Cyrl:]]".into()
+                )
+            ))
+        );
+        assert_eq!(
+            setting("[reorder Latn ]"),
+            Err(nom::Err::Error(nom::error::Error::new(
+                " ]",
+                nom::error::ErrorKind::Char
+            )))
+        );
+    }
+
     #[test]
     fn test_comment() {
         assert_eq!(
@@ -527,4 +785,126 @@ mod test {
             )),
         )
     }
+
+    // Pieces of rules that are likely to confuse the parser when combined
+    fn rule_like() -> impl Strategy<Value = String> {
+        let piece = prop_oneof![
+            Just("&"),
+            Just("<"),
+            Just("<<<<<"),
+            Just("<*"),
+            Just("=*"),
+            Just("="),
+            Just("|"),
+            Just("/"),
+            Just("'"),
+            Just("''"),
+            Just("\\"),
+            Just("\\u00"),
+            Just("\\U0010FFFF"),
+            Just("-"),
+            Just("["),
+            Just("]"),
+            Just("[before 1]"),
+            Just("[before 9]"),
+            Just("[last regular]"),
+            Just("[reorder"),
+            Just("#"),
+            Just("\n"),
+            Just(" "),
+            Just("a"),
+            Just("b"),
+            Just("\u{301}"),
+            Just("\u{10ffff}"),
+        ];
+        prop::collection::vec(piece, 0..24).prop_map(|pieces| pieces.concat())
+    }
+
+    proptest! {
+        #[test]
+        fn rules_parse_without_panicking(rules in rule_like()) {
+            let mut table = CollationElementTable::from(
+                "0061 ; [.0201.0020.0002]\n0062 ; [.0202.0020.0002]\n",
+            )
+            .unwrap();
+            if let Ok(rules) = cldr(&rules) {
+                let _ = table.apply_rules(&rules);
+            }
+        }
+    }
+
+    #[test]
+    fn layered_rules() {
+        let rules = cldr("[alternate shifted] &a < x")
+            .unwrap()
+            .then(cldr("[alternate non-ignorable] &x < y").unwrap());
+        assert_eq!(
+            rules.settings,
+            [
+                ("alternate".into(), "shifted".into()),
+                ("alternate".into(), "non-ignorable".into())
+            ]
+        );
+
+        let mut table = CollationElementTable::from(
+            "0061 ; [.0201.0020.0002]\n0062 ; [.0202.0020.0002]\n0078 ; [.0300.0020.0002]\n0079 ; [.0301.0020.0002]\n",
+        )
+        .unwrap();
+        table.apply_rules(&rules).unwrap();
+        let key = |s| table.generate_sort_key(s);
+        assert!(key("a") < key("x"));
+        assert!(key("x") < key("y"));
+        assert!(key("y") < key("b"));
+    }
+
+    #[test]
+    fn parse_regressions() {
+        for rules in &[
+            "&",
+            "<",
+            "'",
+            "&'",
+            "&a <'",
+            "&a <* a-",
+            "&a <* -",
+            "&[before 1]",
+        ] {
+            assert!(cldr(rules).is_err(), "{}", rules);
+        }
+        // A comment doesn't need a line ending at the end of the rules
+        for rules in &["", "#", "&a < b #", "&a < b # comment", "# a\n# b"] {
+            assert!(cldr(rules).is_ok(), "{}", rules);
+        }
+    }
+
+    // Rule strings of locales in ICU and CLDR, some shortened
+    const LOCALE_RULES: &[(&str, &str)] = &[
+        ("da", "[caseFirst upper]\n&D<<đ<<<Đ<<ð<<<Ð\n&t<<<þ/h\n&T<<<Þ/H\n&Y<<ü<<<Ü<<ű<<<Ű\n&[before 1]ǀ<æ<<<Æ<<ä<<<Ä<ø<<<Ø<<ö<<<Ö<<ő<<<Ő<<œ<<<Œ<å<<<Å<<<aa<<<Aa<<<AA"),
+        ("de-u-co-phonebk", "&AE<<ä<<<Ä\n&OE<<ö<<<Ö\n&UE<<ü<<<Ü"),
+        ("es", "&N<ñ<<<Ñ"),
+        ("fr-CA", "[backwards 2]"),
+        ("hu", "&C<cs<<<Cs<<<CS\n&D<dz<<<Dz<<<DZ\n&DZ<dzs<<<Dzs<<<DZS\n&cs<<<ccs/cs\n&Cs<<<Ccs/cs\n&CS<<<CCS/CS"),
+        ("ja", "&ぁ=ぁ|ー=ァ|ー=ｧ|ー\n&あ=あ|ー=ア|ー=ｱ|ー"),
+        ("ko", "&가<<*伽佳假價加可呵哥嘉嫁家暇架枷柯歌珂痂稼苛茄街袈訶賈跏軻迦駕"),
+        ("ru", "[suppressContractions [Љ-ґ]]\n[optimize [Ά-ώ]]"),
+        ("sv", "&D<<đ<<<Đ<<ð<<<Ð\n&t<<<þ/h\n&T<<<Þ/H\n&Y<<ü<<<Ü<<ű<<<Ű\n&[before 1]ǀ<å<<<Å<ä<<<Ä<<æ<<<Æ<<ę<<<Ę<ö<<<Ö<<ø<<<Ø<<ő<<<Ő<<œ<<<Œ<<ô<<<Ô"),
+        ("zh-u-co-pinyin", "[reorder Hani Bopo]\n&[before 2]a<<ā<<<Ā<<á<<<Á<<ǎ<<<Ǎ<<à<<<À\n&[last regular]<*阿呵吖嗄腌锕錒"),
+        ("und", "# Consecutive resets and a comment at the end\n&a &b < \\u00e6 &'\\u0020' < x # end"),
+    ];
+
+    #[test]
+    fn locale_rules() {
+        for (locale, rules) in LOCALE_RULES {
+            let parsed = cldr(rules);
+            assert!(parsed.is_ok(), "{}: {:?}", locale, parsed);
+            #[cfg(feature = "bundled-ducet")]
+            CollationElementTable::default()
+                .apply_rules(&parsed.unwrap())
+                .unwrap_or_else(|e| panic!("{}: {}", locale, e));
+        }
+        assert_eq!(
+            cldr("[backwards 2]").unwrap().settings,
+            [("backwards".into(), "2".into())]
+        );
+    }
 }
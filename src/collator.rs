@@ -0,0 +1,2580 @@
+use crate::{
+    cache::KeyCache,
+    collation_rules::{self, Rule, SequenceElement},
+    reorder::Reordering,
+    BuildWarning, CollationElement, CollationElementTable, CollationElements, Level, SortKey,
+    TailoringError,
+};
+#[cfg(feature = "bundled-ducet")]
+use crate::{locale, Error};
+#[cfg(all(feature = "bundled-ducet", feature = "std"))]
+use std::path::Path;
+#[cfg(feature = "bundled-ducet")]
+use std::sync::OnceLock;
+use std::{
+    borrow::Cow,
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashSet},
+    ffi::OsStr,
+    fmt, iter,
+    sync::Mutex,
+};
+use unic_normal::StrNormalForm;
+use unic_ucd_category::GeneralCategory;
+
+/// How variable collation elements (spaces, punctuation, ...) are weighted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VariableWeighting {
+    /// Variable elements are weighted like any other element.
+    #[default]
+    NonIgnorable,
+    /// Variable elements are ignored on the first three levels and only
+    /// distinguished on the quaternary level.
+    Shifted,
+    /// Like `Shifted`, but trailing quaternary weights of non-variable
+    /// elements are trimmed, so strings without variable elements have an
+    /// empty quaternary level.
+    ShiftTrimmed,
+}
+
+/// The last group of characters that is treated as variable. The groups are
+/// ordered, so choosing a group also makes all groups before it variable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MaxVariable {
+    Space,
+    // Like the CLDR root collation
+    #[default]
+    Punct,
+    Symbol,
+    Currency,
+}
+
+impl MaxVariable {
+    // Whether `c` belongs to the group that directly follows this one
+    fn is_in_next_group(self, c: char) -> bool {
+        let gc = GeneralCategory::of(c);
+        match self {
+            MaxVariable::Space => gc.is_punctuation(),
+            MaxVariable::Punct => gc.is_symbol() && gc != GeneralCategory::CurrencySymbol,
+            MaxVariable::Symbol => gc == GeneralCategory::CurrencySymbol,
+            MaxVariable::Currency => gc == GeneralCategory::DecimalNumber,
+        }
+    }
+}
+
+/// The number of levels that are compared.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Strength {
+    /// Only base letters, so `a`, `A` and `\u{e1}` are equal
+    Primary,
+    /// Accents, so only `a` and `A` are equal
+    Secondary,
+    /// Case and variants
+    Tertiary,
+    /// The variable elements that are shifted by
+    /// [`VariableWeighting::Shifted`]. This compares all levels of the sort
+    /// key.
+    #[default]
+    Quaternary,
+    /// The code points of the normalized strings, which breaks ties between
+    /// strings that are equal on all other levels
+    Identical,
+}
+
+/// The decomposition that strings are normalized with before collation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Decomposition {
+    /// Canonical decomposition (NFD), as UCA prescribes
+    #[default]
+    Canonical,
+    /// Compatibility decomposition (NFKD), which also folds characters like
+    /// `ﬁ`, `①` and full-width letters to their plain forms. This departs
+    /// from UCA, but is useful for matching in search.
+    Compatibility,
+}
+
+/// The result of [`Collator::compare_tristate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tristate {
+    /// The strings differ at the strength of the collator.
+    Ordered(Ordering),
+    /// The strings are equal at the strength of the collator, but only
+    /// canonically equivalent strings are identical. The identical level can
+    /// be used to order them, like with [`Strength::Identical`].
+    Equal,
+    /// The strings are canonically equivalent.
+    Identical,
+}
+
+/// A distinction made on the tertiary level, see
+/// [`Collator::set_tertiary_feature`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TertiaryFeature {
+    /// Uppercase and lowercase letters, like `A` and `a`
+    Case,
+    /// Full and half width forms, like `\u{ff21}` and `A`
+    Width,
+    /// Hiragana and katakana, like `\u{3042}` and `\u{30a2}`
+    Kana,
+    /// Compatibility variants, like circled or superscript letters
+    CompatVariant,
+}
+
+impl TertiaryFeature {
+    // In the order in which the features are removed from a weight
+    const ALL: [TertiaryFeature; 4] = [
+        TertiaryFeature::CompatVariant,
+        TertiaryFeature::Width,
+        TertiaryFeature::Kana,
+        TertiaryFeature::Case,
+    ];
+
+    // Maps a tertiary weight to the weight without this distinction, based on
+    // the tertiary weight assignments of DUCET (table 14 of UTS #10)
+    fn remove(self, tertiary: u16) -> u16 {
+        match (self, tertiary) {
+            (TertiaryFeature::Case, 0x08..=0x0C) => tertiary - 6,
+            (TertiaryFeature::Case, 0x1D) => 0x1C,
+            (TertiaryFeature::Width, 0x03) => 0x02,
+            (TertiaryFeature::Width, 0x09) => 0x08,
+            (TertiaryFeature::Width, 0x10) => 0x0F,
+            (TertiaryFeature::Width, 0x12) => 0x11,
+            (TertiaryFeature::Kana, 0x0F) => 0x0D,
+            (TertiaryFeature::Kana, 0x11) => 0x0E,
+            (TertiaryFeature::CompatVariant, 0x04..=0x06)
+            | (TertiaryFeature::CompatVariant, 0x14..=0x1C)
+            | (TertiaryFeature::CompatVariant, 0x1E) => 0x02,
+            (TertiaryFeature::CompatVariant, 0x0A..=0x0C)
+            | (TertiaryFeature::CompatVariant, 0x1D) => 0x08,
+            (TertiaryFeature::CompatVariant, 0x13) => 0x11,
+            _ => tertiary,
+        }
+    }
+}
+
+/// Which case sorts first, see [`Collator::set_case_first`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaseFirst {
+    /// The order of the table, which in DUCET puts lowercase first but can
+    /// let other tertiary differences come before case
+    #[default]
+    Off,
+    /// Uppercase before lowercase, so `A` sorts before `a`
+    Upper,
+    /// Lowercase before uppercase, so `a` sorts before `A`
+    Lower,
+}
+
+/// The script of the labels of an alphabetic index, see
+/// [`Collator::first_primary_label`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndexScript {
+    /// The letters `A` to `Z`
+    #[default]
+    Latin,
+    /// The 14 basic Hangul consonants `ㄱ` to `ㅎ`, by leading consonant
+    Hangul,
+}
+
+impl IndexScript {
+    fn labels(self) -> Vec<char> {
+        match self {
+            IndexScript::Latin => ('A'..='Z').collect(),
+            IndexScript::Hangul => "\u{3131}\u{3134}\u{3137}\u{3139}\u{3141}\u{3142}\u{3145}\
+                                    \u{3147}\u{3148}\u{314a}\u{314b}\u{314c}\u{314d}\u{314e}"
+                .chars()
+                .collect(),
+        }
+    }
+
+    // The first letter of the script following this one, which ends the
+    // bucket of the last label
+    fn end(self) -> char {
+        match self {
+            IndexScript::Latin => '\u{3b1}',
+            IndexScript::Hangul => '\u{3041}',
+        }
+    }
+}
+
+// The version of UTS #10 and of the Unicode data that the algorithm follows
+const UCA_VERSION: &str = "15.1.0";
+
+// The quaternary weight of hiragana with `[hiraganaQ on]`, which is between
+// the weights of variable elements and the one of all other elements
+const HIRAGANA_QUATERNARY: u16 = 0xFFFE;
+
+// The weight of the case that sorts first on the case level, the other case
+// gets the next weight
+const FIRST_CASE: u16 = 0x0001;
+
+// The bundled DUCET, which is parsed the first time it is needed and then
+// shared by the root collator and the locales without tailoring
+#[cfg(feature = "bundled-ducet")]
+pub(crate) fn root_table() -> &'static CollationElementTable {
+    static ROOT: OnceLock<CollationElementTable> = OnceLock::new();
+    ROOT.get_or_init(CollationElementTable::default)
+}
+
+/// Compares strings using a collation element table and a set of options.
+pub struct Collator {
+    // Borrowed for the shared root table and the tables of bundled locales
+    table: Cow<'static, CollationElementTable>,
+    variable_weighting: VariableWeighting,
+    // The highest primary weight that is treated as variable
+    variable_top: u16,
+    strength: Strength,
+    ignored_tertiary: Vec<TertiaryFeature>,
+    ignored_chars: HashSet<char>,
+    decomposition: Decomposition,
+    max_key_len: Option<usize>,
+    hiragana_quaternary: bool,
+    case_level: bool,
+    case_first: CaseFirst,
+    numeric: bool,
+    fold_to_base: bool,
+    reordering: Option<Reordering>,
+    cache: Option<Mutex<KeyCache>>,
+    // The first primary weights of the labels and the end of the last bucket
+    index_script: IndexScript,
+    index: Vec<(u16, char)>,
+    index_end: u16,
+}
+
+impl Collator {
+    pub fn new(table: CollationElementTable) -> Self {
+        Self::from_cow(Cow::Owned(table))
+    }
+
+    /// A collator for the root collation, using the bundled DUCET. The table
+    /// is parsed the first time this is called and shared by all root
+    /// collators after that, which makes them cheap to create. Initializing
+    /// the table is thread-safe, so this can be called from any thread.
+    #[cfg(feature = "bundled-ducet")]
+    pub fn root() -> Self {
+        Self::from_cow(Cow::Borrowed(root_table()))
+    }
+
+    fn from_cow(table: Cow<'static, CollationElementTable>) -> Self {
+        let mut collator = Self {
+            variable_top: variable_top(&table, MaxVariable::default()),
+            table,
+            variable_weighting: VariableWeighting::default(),
+            strength: Strength::default(),
+            ignored_tertiary: Vec::new(),
+            ignored_chars: HashSet::new(),
+            decomposition: Decomposition::default(),
+            max_key_len: None,
+            hiragana_quaternary: false,
+            case_level: false,
+            case_first: CaseFirst::default(),
+            numeric: false,
+            fold_to_base: false,
+            reordering: None,
+            cache: None,
+            index_script: IndexScript::default(),
+            index: Vec::new(),
+            index_end: 0,
+        };
+        collator.set_index_script(IndexScript::default());
+        collator
+    }
+
+    /// Creates a collator for `base` tailored with a CLDR rule string, like
+    /// `"&a < x < y"` to sort `x` and `y` directly after `a`.
+    pub fn with_tailoring(
+        base: CollationElementTable,
+        rules: &str,
+    ) -> Result<Self, TailoringError> {
+        Self::with_tailoring_warnings(base, rules).map(|(collator, _)| collator)
+    }
+
+    /// Creates a collator like [`Collator::with_tailoring`], but also returns
+    /// a warning for every relation that has no effect at the strength of the
+    /// collator, like the `<<<` in `[strength 1] &a <<< x`.
+    pub fn with_tailoring_warnings(
+        mut base: CollationElementTable,
+        rules: &str,
+    ) -> Result<(Self, Vec<BuildWarning>), TailoringError> {
+        let rules = collation_rules::cldr(rules).map_err(|_| TailoringError::Syntax)?;
+        base.apply_rules(&rules)?;
+        let mut collator = Self::new(base);
+        collator.apply_settings(&rules.settings)?;
+
+        let mut warnings: Vec<_> = rules
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                let (level, sequence) = match rule {
+                    Rule::Increment {
+                        level, sequence, ..
+                    } => (*level, sequence.clone()),
+                    Rule::MultiIncrement {
+                        level,
+                        multisequence,
+                    } => (*level, multisequence.iter().map(list_element).collect()),
+                    _ => return None,
+                };
+                let strength = relation_strength(level);
+                if strength > collator.strength {
+                    Some(BuildWarning { sequence, strength })
+                } else {
+                    None
+                }
+            })
+            .collect();
+        // Without a case level, the case order is part of the tertiary level
+        if collator.case_first != CaseFirst::Off
+            && !collator.case_level
+            && collator.strength < Strength::Tertiary
+        {
+            warnings.extend(
+                rules
+                    .settings
+                    .iter()
+                    .filter(|(key, _)| key == "caseFirst")
+                    .map(|(key, value)| BuildWarning {
+                        sequence: format!("[{} {}]", key, value),
+                        strength: Strength::Tertiary,
+                    }),
+            );
+        }
+        Ok((collator, warnings))
+    }
+
+    /// Creates a collator for a BCP 47 locale identifier like `de-DE` or
+    /// `de-DE-u-co-phonebk`, using the collation data bundled with the crate.
+    /// Locales without bundled data get the root collation.
+    #[cfg(feature = "bundled-ducet")]
+    pub fn for_locale(id: &str) -> Result<Self, Error> {
+        let (table, settings) = locale::tailored(id)?;
+        let mut collator = Self::from_cow(table);
+        collator.apply_settings(&settings)?;
+        Ok(collator)
+    }
+
+    /// Creates a collator for a BCP 47 locale identifier like `de-DE`, using
+    /// the collation data in `dir`, which is either a CLDR release or its
+    /// `common/collation` directory. Locales without data in `dir` get the
+    /// root collation.
+    #[cfg(all(feature = "bundled-ducet", feature = "std"))]
+    pub fn for_locale_in_cldr_dir(dir: impl AsRef<Path>, id: &str) -> Result<Self, Error> {
+        let (table, settings) = locale::tailored_from_cldr_dir(dir.as_ref(), id)?;
+        let mut collator = Self::from_cow(table);
+        collator.apply_settings(&settings)?;
+        Ok(collator)
+    }
+
+    // Applies the options of the settings of a rule string, like
+    // `[alternate shifted]` or `[reorder Grek others]`. Other settings are
+    // ignored, but the ones that are known have to have a valid value.
+    fn apply_settings(&mut self, settings: &[(String, String)]) -> Result<(), TailoringError> {
+        for (key, value) in settings {
+            let invalid = || TailoringError::InvalidSetting {
+                key: key.clone(),
+                value: value.clone(),
+            };
+            let on = || match value.as_str() {
+                "on" => Ok(true),
+                "off" => Ok(false),
+                _ => Err(invalid()),
+            };
+            match (key.as_str(), value.as_str()) {
+                ("alternate", "shifted") => self.set_variable_weighting(VariableWeighting::Shifted),
+                ("alternate", "non-ignorable") => {
+                    self.set_variable_weighting(VariableWeighting::NonIgnorable)
+                }
+                ("hiraganaQ", _) => self.set_hiragana_quaternary(on()?),
+                ("caseLevel", _) => self.set_case_level(on()?),
+                ("numericOrdering", _) => self.set_numeric(on()?),
+                ("caseFirst", "upper") => self.set_case_first(CaseFirst::Upper),
+                ("caseFirst", "lower") => self.set_case_first(CaseFirst::Lower),
+                ("caseFirst", "off") => self.set_case_first(CaseFirst::Off),
+                ("strength", level @ ("1" | "2" | "3" | "4")) => {
+                    self.set_strength(relation_strength(level.parse().unwrap()))
+                }
+                ("strength", "I") => self.set_strength(Strength::Identical),
+                ("alternate", _) | ("caseFirst", _) | ("strength", _) => return Err(invalid()),
+                ("reorder", codes) => {
+                    self.set_reordering(&codes.split_whitespace().collect::<Vec<_>>())?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Caches the sort keys of the `capacity` most recently used strings,
+    /// which speeds up comparing the same strings repeatedly, like a sort does.
+    /// The cache is behind a mutex, so the collator can still be shared
+    /// between threads.
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache = Some(Mutex::new(KeyCache::new(capacity)));
+        self
+    }
+
+    /// The version of UTS #10 that the collator implements, like `15.1.0`.
+    /// The implicit weights of unassigned characters follow this version
+    /// unless the table has an older `@version`.
+    pub fn uca_version(&self) -> &'static str {
+        UCA_VERSION
+    }
+
+    /// The version of the collation element table, from its `@version` line.
+    pub fn data_version(&self) -> Option<&str> {
+        self.table.header().version()
+    }
+
+    // Must be called whenever an option changes the generated sort keys
+    fn clear_cache(&mut self) {
+        if let Some(cache) = &mut self.cache {
+            cache.get_mut().unwrap().clear();
+        }
+    }
+
+    pub fn set_variable_weighting(&mut self, variable_weighting: VariableWeighting) {
+        self.variable_weighting = variable_weighting;
+        self.clear_cache();
+    }
+
+    /// Sets the last group of characters that is variable. Elements are
+    /// reclassified by their primary weight, so this overrides the variable
+    /// markers in the table.
+    pub fn set_max_variable(&mut self, max_variable: MaxVariable) {
+        self.variable_top = variable_top(&self.table, max_variable);
+        self.clear_cache();
+    }
+
+    /// Sets the number of levels that are compared. The levels below the
+    /// strength are left out of sort keys.
+    pub fn set_strength(&mut self, strength: Strength) {
+        self.strength = strength;
+        self.clear_cache();
+    }
+
+    /// Sets whether `feature` is distinguished on the tertiary level, which
+    /// it is by default. For example, disabling [`TertiaryFeature::Case`]
+    /// ignores case while accents and width still count. This assumes the
+    /// tertiary weights of DUCET.
+    pub fn set_tertiary_feature(&mut self, feature: TertiaryFeature, enabled: bool) {
+        self.ignored_tertiary.retain(|&f| f != feature);
+        if !enabled {
+            self.ignored_tertiary.push(feature);
+        }
+        self.clear_cache();
+    }
+
+    /// Configures the collator to ignore case but not accents. Case is a
+    /// tertiary difference in UCA, so [`Strength::Secondary`] would ignore it,
+    /// but also width and the other tertiary differences. Instead, this keeps
+    /// [`Strength::Tertiary`] and only removes [`TertiaryFeature::Case`] from
+    /// the tertiary weights.
+    ///
+    /// ```
+    /// let collator = collate::Collator::default().case_insensitive_accent_sensitive();
+    /// assert!(collator.is_equal("R\u{e9}sum\u{e9}", "r\u{e9}sum\u{e9}"));
+    /// assert!(!collator.is_equal("Resume", "R\u{e9}sum\u{e9}"));
+    /// ```
+    pub fn case_insensitive_accent_sensitive(mut self) -> Self {
+        self.set_strength(Strength::Tertiary);
+        self.set_tertiary_feature(TertiaryFeature::Case, false);
+        self
+    }
+
+    /// Sets whether hiragana and katakana are only told apart on the
+    /// quaternary level, like the CLDR setting `[hiraganaQ on]`. The
+    /// difference is removed from the tertiary weights, and hiragana get a
+    /// lower quaternary weight than other characters, so `\u{3042}` sorts
+    /// before `\u{30a2}` at [`Strength::Quaternary`] but is equal to it
+    /// at [`Strength::Tertiary`].
+    pub fn set_hiragana_quaternary(&mut self, enabled: bool) {
+        self.hiragana_quaternary = enabled;
+        self.clear_cache();
+    }
+
+    /// Sets whether case is compared on a level of its own between the
+    /// secondary and tertiary levels, like the CLDR setting `[caseLevel on]`.
+    /// The case level is compared at every strength, so at
+    /// [`Strength::Primary`] this ignores accents but not case. Case is then
+    /// removed from the tertiary level.
+    pub fn set_case_level(&mut self, enabled: bool) {
+        self.case_level = enabled;
+        self.clear_cache();
+    }
+
+    /// Sets which case sorts first, like the CLDR setting `[caseFirst upper]`.
+    /// This orders the case level if there is one, or else the tertiary
+    /// level, where case then counts before the other tertiary differences.
+    /// Without a case level, this has no effect below [`Strength::Tertiary`].
+    pub fn set_case_first(&mut self, case_first: CaseFirst) {
+        self.case_first = case_first;
+        self.clear_cache();
+    }
+
+    /// Sets whether numbers are sorted by their value instead of digit by
+    /// digit, like the CLDR setting `[numericOrdering on]`, so `"file9"`
+    /// sorts before `"file10"`. This works for the decimal digits of every
+    /// script, and a number can mix them. Leading zeros are ignored, and
+    /// digits with the same value are equal up to [`Strength::Identical`].
+    pub fn set_numeric(&mut self, numeric: bool) {
+        self.numeric = numeric;
+        self.clear_cache();
+    }
+
+    /// Sets characters that are ignored completely, like `'` to sort
+    /// "O'Brien" as "OBrien". Unlike variable weighting, this also ignores
+    /// them on the quaternary level. They still count at
+    /// [`Strength::Identical`].
+    pub fn set_ignored_chars(&mut self, chars: impl IntoIterator<Item = char>) {
+        self.ignored_chars = chars.into_iter().collect();
+        self.clear_cache();
+    }
+
+    /// Limits sort keys to `max_key_len` weights, which bounds the time and
+    /// memory needed for huge strings, and gives prefix keys for database
+    /// indexes. The weights are cut off in level order, so the primary
+    /// weights are kept first.
+    ///
+    /// Truncating keeps the order of strings, but strings that only differ
+    /// after the cut off compare equal, like long strings with a long common
+    /// prefix.
+    pub fn set_max_key_len(&mut self, max_key_len: Option<usize>) {
+        self.max_key_len = max_key_len;
+        self.clear_cache();
+    }
+
+    /// Sets the decomposition that strings are normalized with. With
+    /// [`Decomposition::Compatibility`], `"ﬁ"` collates equal to `"fi"`.
+    pub fn set_decomposition(&mut self, decomposition: Decomposition) {
+        self.decomposition = decomposition;
+        self.clear_cache();
+    }
+
+    /// Sets whether strings are folded to base letters before they are
+    /// compared, which is meant for search and for grouping in buckets. This
+    /// is lossy: accents are removed and letters like `ø`, `ł` and `ß` are
+    /// replaced by `o`, `l` and `ss`, even when a tailoring sorts them as
+    /// separate letters. So `"S\u{f8}ren"` matches `"Soren"` in Danish,
+    /// where `ø` sorts after `z`.
+    pub fn set_fold_to_base(&mut self, fold_to_base: bool) {
+        self.fold_to_base = fold_to_base;
+        self.clear_cache();
+    }
+
+    // Applies the compatibility decomposition and the folding to base letters
+    // if they are set and removes the ignored characters from `s`. This is
+    // done after decomposing it, so that accents and ignored characters are
+    // also removed from precomposed characters. The canonical decomposition
+    // is left to the collation elements iterator.
+    fn prepare<'s>(&self, s: &'s str) -> Cow<'s, str> {
+        let s = match self.decomposition {
+            Decomposition::Compatibility if !s.is_ascii() => Cow::Owned(s.nfkd().collect()),
+            _ => Cow::Borrowed(s),
+        };
+        // Without folding or ignored characters, which is the default, `s`
+        // isn't decomposed here
+        let fold = self.fold_to_base && !s.is_ascii();
+        let ignore =
+            !self.ignored_chars.is_empty() && s.nfd().any(|c| self.ignored_chars.contains(&c));
+        if !fold && !ignore {
+            return s;
+        }
+        let mut prepared = String::with_capacity(s.len());
+        for c in s.nfd().filter(|c| !self.ignored_chars.contains(c)) {
+            match base_letters(c) {
+                Some(base) if fold => prepared.push_str(base),
+                None if fold && is_diacritic(c) => {}
+                _ => prepared.push(c),
+            }
+        }
+        Cow::Owned(prepared)
+    }
+
+    /// Reorders the scripts, given by their ISO 15924 codes. The listed
+    /// scripts sort first, in the given order, followed by the other scripts
+    /// in their usual order. Listing `others` (or `Zzzz`) puts the other
+    /// scripts at that place instead, so `["others", "Latn"]` sorts Latin
+    /// last. An empty list restores the usual order.
+    ///
+    /// Spaces, punctuation, symbols and digits always sort before the
+    /// scripts, and ideographs after them.
+    pub fn set_reordering(&mut self, codes: &[&str]) -> Result<(), TailoringError> {
+        self.reordering = match codes {
+            [] => None,
+            _ => Some(Reordering::new(&self.table, codes)?),
+        };
+        self.set_index_script(self.index_script);
+        self.clear_cache();
+        Ok(())
+    }
+
+    // The collation elements of `s`, with the scripts reordered
+    fn elements<'s>(&'s self, s: &'s str) -> impl Iterator<Item = CollationElement> + 's {
+        let elements = if self.numeric {
+            CollationElements::numeric(&self.table, s)
+        } else {
+            CollationElements::from_str(&self.table, s)
+        };
+        elements.map(move |mut elem| {
+            if let Some(reordering) = &self.reordering {
+                elem.primary = reordering.apply(elem.primary);
+            }
+            elem
+        })
+    }
+
+    /// Sets the script of the labels returned by
+    /// [`first_primary_label`](Self::first_primary_label).
+    pub fn set_index_script(&mut self, index_script: IndexScript) {
+        self.index_script = index_script;
+        let mut index: Vec<_> = index_script
+            .labels()
+            .into_iter()
+            .filter_map(|c| Some((self.first_primary(c.encode_utf8(&mut [0; 4]))?, c)))
+            .collect();
+        index.sort_unstable();
+        self.index = index;
+        // Scripts are reordered as a whole, so the bucket still ends right
+        // after the weight before the next script
+        let end =
+            CollationElements::from_str(&self.table, index_script.end().encode_utf8(&mut [0; 4]))
+                .map(|elem| elem.primary)
+                .find(|&p| p != 0);
+        self.index_end = match (end, &self.reordering) {
+            (Some(end), Some(reordering)) => reordering.apply(end - 1) + 1,
+            (Some(end), None) => end,
+            (None, _) => u16::MAX,
+        };
+    }
+
+    /// The labels of the alphabetic index, in collation order.
+    pub fn bucket_labels(&self) -> Vec<char> {
+        self.index.iter().map(|&(_, c)| c).collect()
+    }
+
+    /// Returns the label of the alphabetic index bucket that `s` belongs to,
+    /// like `A` for both "apple" and "Ápple". Strings that sort before the
+    /// first label or after the script of the labels have no bucket.
+    pub fn first_primary_label(&self, s: &str) -> Option<char> {
+        let primary = self.first_primary(s)?;
+        if primary >= self.index_end {
+            return None;
+        }
+        let i = self.index.partition_point(|&(p, _)| p <= primary);
+        Some(self.index.get(i.checked_sub(1)?)?.1)
+    }
+
+    // The first non-zero primary weight of `s`
+    fn first_primary(&self, s: &str) -> Option<u16> {
+        let s = self.prepare(s);
+        let first = self.elements(&s).map(|elem| elem.primary).find(|&p| p != 0);
+        first
+    }
+
+    /// Returns only the primary level of the sort key of `s`, which is
+    /// cheaper to compute. Strings that only differ in case or accents have
+    /// the same primary key, so it works well as a key for grouping them.
+    pub fn primary_key(&self, s: &str) -> Vec<u16> {
+        let s = self.prepare(s);
+        let key = self.primaries(&s).collect();
+        key
+    }
+
+    /// The number of primary weights at the start of `a` and `b` that are the
+    /// same, which is where the strings start to differ in a sorted list.
+    /// For example, `"apple"` and `"Apples"` share 5 and `"apple"` and
+    /// `"banana"` none. Characters that are ignored at the primary level,
+    /// like accents, don't count.
+    pub fn common_primary_prefix_len(&self, a: &str, b: &str) -> usize {
+        let (a, b) = (self.prepare(a), self.prepare(b));
+        let len = self
+            .primaries(&a)
+            .zip(self.primaries(&b))
+            .take_while(|(a, b)| a == b)
+            .count();
+        len
+    }
+
+    // The primary weights of a prepared string that are part of its sort key
+    fn primaries<'s>(&'s self, s: &'s str) -> impl Iterator<Item = u16> + 's {
+        self.elements(s).map(|elem| elem.primary).filter(move |&p| {
+            p != 0
+                && (self.variable_weighting == VariableWeighting::NonIgnorable
+                    || p > self.variable_top)
+        })
+    }
+
+    pub fn sort_key(&self, s: &str) -> SortKey {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.generate_sort_key(s, self.strength),
+        };
+        if let Some(key) = cache.lock().unwrap().get(s) {
+            return key;
+        }
+        // Not holding the lock while generating the key
+        let key = self.generate_sort_key(s, self.strength);
+        cache.lock().unwrap().insert(s, key.clone());
+        key
+    }
+
+    fn generate_sort_key(&self, s: &str, strength: Strength) -> SortKey {
+        let mut key = SortKey::new();
+        let mut after_variable = false;
+        let s = self.prepare(s);
+        for mut elem in self.elements(&s) {
+            // Everything after the last primary weight that is kept is cut off
+            if matches!(self.max_key_len, Some(len) if key.primary.len() > len) {
+                break;
+            }
+            let quaternary = match elem.tertiary_class {
+                // Small and normal hiragana in DUCET, before any tailoring
+                0x0D | 0x0E if self.hiragana_quaternary => HIRAGANA_QUATERNARY,
+                _ => 0xFFFF,
+            };
+            // The features are told apart by the tertiary weight the element
+            // had before tailoring, since tailoring can move the weight
+            let mut class = u16::from(elem.tertiary_class);
+            for feature in &TertiaryFeature::ALL {
+                if self.ignored_tertiary.contains(feature)
+                    || (*feature == TertiaryFeature::Kana && self.hiragana_quaternary)
+                {
+                    let removed = feature.remove(class);
+                    if removed != class {
+                        elem.tertiary = self.table.moved_tertiary(removed);
+                        class = removed;
+                    }
+                }
+            }
+            let caseless = TertiaryFeature::Case.remove(class);
+            let upper = caseless != class;
+            let case = if upper == (self.case_first == CaseFirst::Upper) {
+                FIRST_CASE
+            } else {
+                FIRST_CASE + 1
+            };
+            if self.case_level {
+                if upper {
+                    elem.tertiary = self.table.moved_tertiary(caseless);
+                }
+            } else if self.case_first != CaseFirst::Off && elem.tertiary != 0 {
+                // Case becomes the most significant part of the weight
+                elem.tertiary |= case << 8;
+            }
+            let has_case = self.case_level && elem.primary != 0;
+            if self.variable_weighting == VariableWeighting::NonIgnorable {
+                // The quaternary level then only tells apart hiragana
+                let ignorable = elem.primary == 0 && elem.secondary == 0 && elem.tertiary == 0;
+                if self.hiragana_quaternary && !ignorable {
+                    key.quaternary.push(quaternary);
+                }
+                if has_case {
+                    key.case.push(case);
+                }
+                key.push(&elem);
+            } else if elem.primary != 0 && elem.primary <= self.variable_top {
+                key.quaternary.push(elem.primary);
+                after_variable = true;
+            } else if elem.primary == 0
+                && (after_variable || (elem.secondary == 0 && elem.tertiary == 0))
+            {
+                // Ignorables following a variable element and completely
+                // ignorable elements are ignored on all levels
+            } else {
+                if has_case {
+                    key.case.push(case);
+                }
+                key.push(&elem);
+                key.quaternary.push(quaternary);
+                after_variable = false;
+            }
+        }
+        if self.variable_weighting == VariableWeighting::ShiftTrimmed {
+            while key.quaternary.last() == Some(&0xFFFF) {
+                key.quaternary.pop();
+            }
+        }
+        if strength < Strength::Quaternary {
+            key.quaternary.clear();
+        }
+        if strength < Strength::Tertiary {
+            key.tertiary.clear();
+        }
+        if strength < Strength::Secondary {
+            key.secondary.clear();
+        }
+        if let Some(len) = self.max_key_len {
+            key.truncate(len);
+        }
+        key
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        compare_by_keys(a, b, |s| self.sort_key(s), self.strength)
+    }
+
+    /// Compares like [`Collator::compare`], but orders strings that it finds
+    /// equal by their code points, so only equal strings compare equal. This
+    /// gives a total order for deduplicating or sorting deterministically.
+    ///
+    /// Unlike [`Strength::Identical`], which compares the NFD forms, this
+    /// also tells apart canonically equivalent strings like `"\u{e1}"` and
+    /// `"a\u{301}"`.
+    pub fn total_cmp(&self, a: &str, b: &str) -> Ordering {
+        self.compare(a, b).then_with(|| a.cmp(b))
+    }
+
+    /// Returns a view of the collator that compares at `strength` instead.
+    /// The view borrows the table, so this is cheap, unlike building a new
+    /// collator.
+    pub fn with_strength(&self, strength: Strength) -> CollatorView<'_> {
+        CollatorView {
+            collator: self,
+            strength,
+        }
+    }
+
+    /// Pairs `s` with its sort key, so it can be used as the key of a
+    /// [`BTreeMap`](std::collections::BTreeMap) that iterates in collation
+    /// order.
+    pub fn collated(&self, s: impl Into<String>) -> CollatedString {
+        let string = s.into();
+        CollatedString {
+            key: self.sort_key(&string),
+            string,
+        }
+    }
+
+    /// Returns [`Collator::compare`] as a function that can be passed to
+    /// [`slice::sort_by`] or [`slice::sort_unstable_by`] directly.
+    ///
+    /// ```
+    /// let collator = collate::Collator::default();
+    /// let mut v = ["b", "A", "a"];
+    /// v.sort_unstable_by(collator.cmp_fn());
+    /// assert_eq!(v, ["a", "A", "b"]);
+    /// ```
+    pub fn cmp_fn(&self) -> impl Fn(&&str, &&str) -> Ordering + '_ {
+        move |a, b| self.compare(a, b)
+    }
+
+    /// Whether the strings are equal at the strength of the collator.
+    pub fn is_equal(&self, a: &str, b: &str) -> bool {
+        self.compare(a, b) == Ordering::Equal
+    }
+
+    /// Compares `s` to a sort key that was generated before, like one that is
+    /// stored with a string. Only the keys are compared, so at
+    /// [`Strength::Identical`] this doesn't tell apart strings that only
+    /// differ in their code points.
+    pub fn compare_to_key(&self, s: &str, key: &SortKey) -> Ordering {
+        self.sort_key(s).cmp(key)
+    }
+
+    /// Binary searches items sorted by the sort keys that `key` returns for
+    /// `s`, like [`slice::binary_search_by`]. The sort key of `s` is only
+    /// generated once.
+    pub fn binary_search_by_sort_key<T>(
+        &self,
+        items: &[T],
+        s: &str,
+        key: impl Fn(&T) -> &SortKey,
+    ) -> Result<usize, usize> {
+        let query = self.sort_key(s);
+        items.binary_search_by(|item| key(item).cmp(&query))
+    }
+
+    /// Sorts items by a string that `key` extracts from them, like the name
+    /// of a record. The sort key of every item is generated once, instead of
+    /// twice for every comparison. The sort is stable.
+    pub fn sort_by<T>(&self, items: &mut [T], key: impl Fn(&T) -> &str) {
+        items.sort_by_cached_key(|item| self.full_key(key(item)));
+    }
+
+    /// Merges inputs that are each sorted by this collator into one sorted
+    /// sequence, like the sorted runs of an external sort. The inputs are
+    /// read as the merged strings are needed, and equal strings come in the
+    /// order of their inputs.
+    pub fn merge_sorted<'a, I>(&'a self, inputs: Vec<I>) -> impl Iterator<Item = String> + 'a
+    where
+        I: IntoIterator<Item = String>,
+        I::IntoIter: 'a,
+    {
+        let mut inputs: Vec<_> = inputs.into_iter().map(IntoIterator::into_iter).collect();
+        // The next string of every input that isn't exhausted, smallest first
+        let mut heads = BinaryHeap::new();
+        for (i, input) in inputs.iter_mut().enumerate() {
+            if let Some(s) = input.next() {
+                heads.push(Reverse((self.full_key(&s), i, s)));
+            }
+        }
+        iter::from_fn(move || {
+            let Reverse((_, i, s)) = heads.pop()?;
+            if let Some(next) = inputs[i].next() {
+                heads.push(Reverse((self.full_key(&next), i, next)));
+            }
+            Some(s)
+        })
+    }
+
+    // The sort key with the tiebreak of `compare` at identical strength, which
+    // orders like `compare`
+    fn full_key(&self, s: &str) -> (SortKey, String) {
+        let nfd = if self.strength == Strength::Identical {
+            s.nfd().collect()
+        } else {
+            String::new()
+        };
+        (self.sort_key(s), nfd)
+    }
+
+    /// Splits the items into runs of items that are equal at the strength of
+    /// the collator. Sorted items are split into their equivalence classes.
+    pub fn partition_equal<'a, T: AsRef<str>>(
+        &'a self,
+        items: &'a [T],
+    ) -> impl Iterator<Item = &'a [T]> + 'a {
+        items.chunk_by(move |a, b| self.is_equal(a.as_ref(), b.as_ref()))
+    }
+
+    /// Sorts the strings and removes the ones that are equal to another one at
+    /// the strength of the collator. The sort is stable, so of equal strings
+    /// the one that came first is kept.
+    pub fn dedup(&self, items: &mut Vec<String>) {
+        items.sort_by(|a, b| self.compare(a, b));
+        self.dedup_by_collation(items);
+    }
+
+    /// Removes consecutive items that are equal at the strength of the
+    /// collator, keeping the first of them, like [`Vec::dedup`]. The items
+    /// are not sorted first.
+    pub fn dedup_by_collation<T: AsRef<str>>(&self, items: &mut Vec<T>) {
+        items.dedup_by(|a, b| self.is_equal(a.as_ref(), b.as_ref()));
+    }
+
+    /// Compares the strings at the strength of the collator, but tells apart
+    /// strings that are only equal at that strength from identical strings.
+    /// This shows whether a tiebreak on the code points would still order
+    /// them.
+    pub fn compare_tristate(&self, a: &str, b: &str) -> Tristate {
+        match self.sort_key(a).cmp(&self.sort_key(b)) {
+            Ordering::Equal if a.nfd().eq(b.nfd()) => Tristate::Identical,
+            Ordering::Equal => Tristate::Equal,
+            ordering => Tristate::Ordered(ordering),
+        }
+    }
+
+    /// Returns the lowest strength at which `a` and `b` differ, or `None` if
+    /// they are equal even at [`Strength::Identical`]. The other options of
+    /// the collator still apply.
+    pub fn distinguishing_strength(&self, a: &str, b: &str) -> Option<Strength> {
+        let key = |s| self.generate_sort_key(s, Strength::Quaternary);
+        match key(a).compare_detailed(&key(b)).1 {
+            Some(Level::Primary) => Some(Strength::Primary),
+            Some(Level::Secondary) => Some(Strength::Secondary),
+            // The case level is compared at every strength
+            Some(Level::Case) => Some(Strength::Primary),
+            Some(Level::Tertiary) => Some(Strength::Tertiary),
+            Some(Level::Quaternary) => Some(Strength::Quaternary),
+            None if a.nfd().eq(b.nfd()) => None,
+            None => Some(Strength::Identical),
+        }
+    }
+
+    /// Compares two texts given as grapheme clusters, like a selection in an
+    /// editor. The clusters are joined before comparing, because contractions
+    /// can span cluster boundaries, so this is equal to comparing the joined
+    /// strings.
+    pub fn compare_graphemes(&self, a: &[&str], b: &[&str]) -> Ordering {
+        self.compare(&a.concat(), &b.concat())
+    }
+
+    /// Compares two OS strings, such as file names.
+    ///
+    /// Valid UTF-8 is compared exactly like [`Collator::compare`] does. Invalid
+    /// sequences are collated as U+FFFD REPLACEMENT CHARACTER. If that makes
+    /// the strings equal, valid strings come first and invalid strings are
+    /// ordered by their bytes, so the ordering is still deterministic.
+    pub fn compare_os(&self, a: &OsStr, b: &OsStr) -> Ordering {
+        match (a.to_str(), b.to_str()) {
+            (Some(a), Some(b)) => self.compare(a, b),
+            _ => self
+                .compare(&a.to_string_lossy(), &b.to_string_lossy())
+                .then_with(|| a.to_str().is_none().cmp(&b.to_str().is_none()))
+                .then_with(|| a.as_encoded_bytes().cmp(b.as_encoded_bytes())),
+        }
+    }
+
+    /// Explains why `items` sort the way they do: returns a table of the
+    /// items in sorted order, each with the weights of its sort key.
+    ///
+    /// ```text
+    /// "a"  1FA2 | 0020 | 0002 |
+    /// "A"  1FA2 | 0020 | 0008 |
+    /// "b"  1FBC | 0020 | 0002 |
+    /// ```
+    pub fn explain_sort(&self, items: &[&str]) -> String {
+        let mut items: Vec<_> = items.iter().map(|s| (format!("{:?}", s), *s)).collect();
+        items.sort_by(|a, b| self.compare(a.1, b.1));
+        let width = items.iter().map(|(quoted, _)| quoted.chars().count()).max();
+
+        let mut explanation = String::new();
+        for (quoted, s) in &items {
+            explanation.push_str(&format!(
+                "{:width$}  {}\n",
+                quoted,
+                self.sort_key(s).to_hex_string(),
+                width = width.unwrap_or(0)
+            ));
+        }
+        explanation
+    }
+
+    /// Returns `chars` in collation order together with their sort keys,
+    /// which shows exactly how a table or tailoring orders them. Characters
+    /// with equal keys keep their relative order.
+    pub fn order_report(&self, chars: impl IntoIterator<Item = char>) -> Vec<(char, SortKey)> {
+        let mut report: Vec<_> = chars
+            .into_iter()
+            .map(|c| (c, self.sort_key(c.encode_utf8(&mut [0; 4]))))
+            .collect();
+        report.sort_by(|a, b| a.1.cmp(&b.1));
+        report
+    }
+}
+
+/// A collator with overridden options, see [`Collator::with_strength`].
+pub struct CollatorView<'a> {
+    collator: &'a Collator,
+    strength: Strength,
+}
+
+impl CollatorView<'_> {
+    pub fn sort_key(&self, s: &str) -> SortKey {
+        // The cache only holds keys at the strength of the collator
+        if self.strength == self.collator.strength {
+            self.collator.sort_key(s)
+        } else {
+            self.collator.generate_sort_key(s, self.strength)
+        }
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        compare_by_keys(a, b, |s| self.sort_key(s), self.strength)
+    }
+}
+
+/// A string together with its sort key, see [`Collator::collated`].
+///
+/// It is ordered by the sort key, and strings with equal keys by their code
+/// points, so that different strings are never equal. It doesn't implement
+/// `Borrow<str>`, because `str` is ordered differently, which would break
+/// lookups in maps.
+#[derive(Clone, Debug)]
+pub struct CollatedString {
+    string: String,
+    key: SortKey,
+}
+
+impl CollatedString {
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
+
+    pub fn sort_key(&self) -> &SortKey {
+        &self.key
+    }
+
+    pub fn into_string(self) -> String {
+        self.string
+    }
+}
+
+impl AsRef<str> for CollatedString {
+    fn as_ref(&self) -> &str {
+        &self.string
+    }
+}
+
+impl fmt::Display for CollatedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.string)
+    }
+}
+
+impl PartialEq for CollatedString {
+    fn eq(&self, other: &Self) -> bool {
+        self.string == other.string
+    }
+}
+
+impl Eq for CollatedString {}
+
+impl PartialOrd for CollatedString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CollatedString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key
+            .cmp(&other.key)
+            .then_with(|| self.string.cmp(&other.string))
+    }
+}
+
+// Compares strings by their sort keys, ordering strings with equal keys by
+// their code points at the identical strength
+fn compare_by_keys(
+    a: &str,
+    b: &str,
+    key: impl Fn(&str) -> SortKey,
+    strength: Strength,
+) -> Ordering {
+    match key(a).cmp(&key(b)) {
+        Ordering::Equal if strength == Strength::Identical => a.nfd().cmp(b.nfd()),
+        ordering => ordering,
+    }
+}
+
+// The base letters of Latin letters that don't decompose, for folding
+fn base_letters(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{f8}' => "o",
+        '\u{d8}' => "O",
+        '\u{111}' | '\u{f0}' => "d",
+        '\u{110}' | '\u{d0}' => "D",
+        '\u{142}' => "l",
+        '\u{141}' => "L",
+        '\u{127}' => "h",
+        '\u{126}' => "H",
+        '\u{167}' => "t",
+        '\u{166}' => "T",
+        '\u{131}' => "i",
+        '\u{14b}' => "n",
+        '\u{14a}' => "N",
+        '\u{df}' => "ss",
+        '\u{1e9e}' => "SS",
+        '\u{e6}' => "ae",
+        '\u{c6}' => "AE",
+        '\u{153}' => "oe",
+        '\u{152}' => "OE",
+        '\u{fe}' => "th",
+        '\u{de}' => "TH",
+        _ => return None,
+    })
+}
+
+// Whether `c` is in one of the blocks of combining diacritical marks, which
+// are the accents that folding removes. Other marks, like the vowel signs
+// of Indic scripts, are kept.
+fn is_diacritic(c: char) -> bool {
+    matches!(
+        c,
+        '\u{300}'..='\u{36f}'
+            | '\u{1ab0}'..='\u{1aff}'
+            | '\u{1dc0}'..='\u{1dff}'
+            | '\u{20d0}'..='\u{20ff}'
+            | '\u{fe20}'..='\u{fe2f}'
+    )
+}
+
+// The strength at which a relation like `<<<` (level 3) is visible
+fn relation_strength(level: u8) -> Strength {
+    match level {
+        1 => Strength::Primary,
+        2 => Strength::Secondary,
+        3 => Strength::Tertiary,
+        _ => Strength::Quaternary,
+    }
+}
+
+// An element of a `<*` list as it is written in the rules
+fn list_element(element: &SequenceElement) -> String {
+    match element {
+        SequenceElement::Char(c) => c.to_string(),
+        SequenceElement::Range(range) => format!("{}-{}", range.start(), range.end()),
+    }
+}
+
+#[cfg(feature = "bundled-ducet")]
+impl Default for Collator {
+    /// The root collator, see [`Collator::root`].
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+// The highest primary weight in the groups up to and including `max_variable`,
+// which is just below the first primary weight of the next group. Tables
+// without characters from the next group fall back to their variable markers.
+fn variable_top(table: &CollationElementTable, max_variable: MaxVariable) -> u16 {
+    table
+        .iter()
+        .filter_map(|(k, v)| {
+            let mut chars = k.chars();
+            match (chars.next(), chars.next(), v) {
+                (Some(c), None, [elem])
+                    if elem.primary != 0 && max_variable.is_in_next_group(c) =>
+                {
+                    Some(elem.primary - 1)
+                }
+                _ => None,
+            }
+        })
+        .min()
+        .unwrap_or_else(|| {
+            table
+                .iter()
+                .flat_map(|(_, elements)| elements)
+                .filter(|elem| elem.variable)
+                .map(|elem| elem.primary)
+                .max()
+                .unwrap_or(0)
+        })
+}
+
+// The tests use the bundled DUCET
+#[cfg(all(test, feature = "bundled-ducet"))]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    #[test]
+    fn shifted() {
+        let mut collator = Collator::default();
+        let detailed = |c: &Collator, a, b| c.sort_key(a).compare_detailed(&c.sort_key(b));
+
+        assert_eq!(
+            detailed(&collator, "a-b", "ab"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+
+        collator.set_variable_weighting(VariableWeighting::Shifted);
+        assert_eq!(
+            detailed(&collator, "a-b", "ab"),
+            (Ordering::Less, Some(Level::Quaternary))
+        );
+        assert_eq!(
+            detailed(&collator, "a b", "a-b"),
+            (Ordering::Less, Some(Level::Quaternary))
+        );
+        assert_eq!(
+            detailed(&collator, "a-b", "a-B"),
+            (Ordering::Less, Some(Level::Tertiary))
+        );
+    }
+
+    #[test]
+    fn shifted_ignorables() {
+        let mut collator = Collator::default();
+        collator.set_variable_weighting(VariableWeighting::Shifted);
+        collator.set_strength(Strength::Quaternary);
+        let space = collator.table[" "][0].primary;
+        let quaternary = |s| collator.sort_key(s).quaternary;
+
+        // Leading and trailing variables only differ on the quaternary level
+        assert_eq!(quaternary(" a"), [space, 0xFFFF]);
+        assert_eq!(quaternary("a "), [0xFFFF, space]);
+        assert_eq!(
+            collator.distinguishing_strength(" a", "a "),
+            Some(Strength::Quaternary)
+        );
+        assert_eq!(collator.compare(" a", "a"), Ordering::Less);
+        assert_eq!(collator.compare("a", "a "), Ordering::Less);
+
+        // Completely ignorable elements and ignorables following a variable
+        // get no quaternary weight, other ignorables get the highest
+        assert_eq!(quaternary("\0a\0"), [0xFFFF]);
+        assert_eq!(quaternary(" \u{301}a"), [space, 0xFFFF]);
+        assert_eq!(quaternary("\u{301}a"), [0xFFFF, 0xFFFF]);
+        assert_eq!(quaternary("a\u{301}"), [0xFFFF, 0xFFFF]);
+        assert_eq!(collator.compare(" \u{301}a", " a"), Ordering::Equal);
+    }
+
+    #[test]
+    fn empty_and_whitespace() {
+        let mut collator = Collator::default();
+        collator.set_strength(Strength::Quaternary);
+        assert!(CollationElements::from_str(&collator.table, "")
+            .next()
+            .is_none());
+
+        for &weighting in &[
+            VariableWeighting::NonIgnorable,
+            VariableWeighting::Shifted,
+            VariableWeighting::ShiftTrimmed,
+        ] {
+            collator.set_variable_weighting(weighting);
+            assert_eq!(collator.sort_key(""), SortKey::new());
+
+            let mut strings = ["a", " ", "", "\n", "  ", "\t"];
+            strings.sort_by(collator.cmp_fn());
+            assert_eq!(strings, ["", "\t", "\n", " ", "  ", "a"], "{:?}", weighting);
+
+            let keys: Vec<_> = strings.iter().map(|s| collator.sort_key(s)).collect();
+            assert_eq!(keys[0].to_bytes(), [1, 1, 1]);
+            for pair in keys.windows(2) {
+                assert!(pair[0].to_bytes() < pair[1].to_bytes());
+            }
+        }
+
+        // Only the quaternary level tells whitespace apart when shifted
+        assert_eq!(
+            collator.distinguishing_strength(" ", ""),
+            Some(Strength::Quaternary)
+        );
+        collator.set_strength(Strength::Tertiary);
+        assert_eq!(collator.compare(" ", "\n"), Ordering::Equal);
+    }
+
+    #[test]
+    fn max_key_len() {
+        let mut collator = Collator::default();
+        let prefix = "abc".repeat(100);
+        let (a, b) = (format!("{}x", prefix), format!("{}y", prefix));
+        assert_eq!(collator.compare(&a, &b), Ordering::Less);
+
+        collator.set_max_key_len(Some(100));
+        assert_eq!(collator.compare(&a, &b), Ordering::Equal);
+        let key = collator.sort_key(&a);
+        assert_eq!(key.primary.len(), 100);
+        assert!(key.secondary.is_empty());
+        assert_eq!(collator.compare("ab", "b"), Ordering::Less);
+
+        // Lower levels are kept as far as they fit
+        collator.set_max_key_len(Some(5));
+        assert_eq!(collator.sort_key("ab").secondary.len(), 2);
+        assert_eq!(collator.sort_key("ab").tertiary.len(), 1);
+        assert_eq!(collator.compare("ab", "aB"), Ordering::Equal);
+        assert_eq!(collator.compare("ab", "\u{e1}b"), Ordering::Less);
+
+        // Truncation never reverses the order
+        let strings = [
+            "a",
+            "ab",
+            "a\u{301}",
+            "a\u{301}b",
+            "A",
+            "aB",
+            "b",
+            "",
+            "a b",
+        ];
+        let mut sorted = strings;
+        collator.set_max_key_len(None);
+        sorted.sort_by(collator.cmp_fn());
+        for len in 0..8 {
+            collator.set_max_key_len(Some(len));
+            for pair in sorted.windows(2) {
+                assert_ne!(collator.compare(pair[0], pair[1]), Ordering::Greater);
+            }
+        }
+
+        collator.set_max_key_len(None);
+        assert_eq!(collator.compare(&a, &b), Ordering::Less);
+    }
+
+    #[test]
+    fn shift_trimmed() {
+        let mut collator = Collator::default();
+
+        collator.set_variable_weighting(VariableWeighting::Shifted);
+        assert_eq!(collator.compare("ab-c", "abc"), Ordering::Less);
+        assert_eq!(collator.sort_key("abc").quaternary, [0xFFFF; 3]);
+
+        collator.set_variable_weighting(VariableWeighting::ShiftTrimmed);
+        assert_eq!(collator.compare("ab-c", "abc"), Ordering::Greater);
+        assert_eq!(collator.sort_key("abc").quaternary, []);
+        assert_eq!(
+            collator.sort_key("a-bc").quaternary,
+            [0xFFFF, collator.table["-"][0].primary]
+        );
+        assert_eq!(
+            collator
+                .sort_key("abc ")
+                .compare_detailed(&collator.sort_key("abc")),
+            (Ordering::Greater, Some(Level::Quaternary))
+        );
+    }
+
+    #[test]
+    fn alternate_setting() {
+        let tailored = |rules| Collator::with_tailoring(CollationElementTable::default(), rules);
+
+        // Punctuation only counts on the quaternary level
+        let collator = tailored("[alternate shifted] &a < x").unwrap();
+        assert_eq!(collator.variable_weighting, VariableWeighting::Shifted);
+        let key = collator.sort_key("a-b");
+        assert_eq!(key.primary, collator.sort_key("ab").primary);
+        assert_eq!(key.quaternary[1], collator.table["-"][0].primary);
+        assert_eq!(
+            key.compare_detailed(&collator.sort_key("ab")),
+            (Ordering::Less, Some(Level::Quaternary))
+        );
+
+        let collator = tailored("[alternate non-ignorable]").unwrap();
+        assert_eq!(collator.variable_weighting, VariableWeighting::NonIgnorable);
+        assert_eq!(
+            tailored("&a < x").unwrap().variable_weighting,
+            VariableWeighting::NonIgnorable
+        );
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let tailored = |rules| Collator::with_tailoring(CollationElementTable::default(), rules);
+        for (rules, key, value) in [
+            ("[caseFirst foo]", "caseFirst", "foo"),
+            ("[strength 5]", "strength", "5"),
+            ("[alternate none]", "alternate", "none"),
+            ("[caseLevel yes]", "caseLevel", "yes"),
+            ("[hiraganaQ 1] &a < x", "hiraganaQ", "1"),
+        ] {
+            assert_eq!(
+                tailored(rules).err(),
+                Some(TailoringError::InvalidSetting {
+                    key: key.into(),
+                    value: value.into()
+                })
+            );
+        }
+        assert_eq!(
+            tailored("[caseFirst foo]").err().unwrap().to_string(),
+            "invalid value \"foo\" for [caseFirst]"
+        );
+        // Settings this crate doesn't know are still ignored
+        assert!(tailored("[backwards 2]").is_ok());
+        assert!(!tailored("[numericOrdering off]").unwrap().numeric);
+    }
+
+    #[test]
+    fn max_variable() {
+        let mut collator = Collator::default();
+        collator.set_variable_weighting(VariableWeighting::Shifted);
+
+        // Punctuation is variable by default, but symbols are not
+        assert_eq!(collator.compare("a-c", "ab"), Ordering::Greater);
+        assert_eq!(collator.compare("a+c", "ab"), Ordering::Less);
+
+        collator.set_max_variable(MaxVariable::Space);
+        assert_eq!(collator.compare("a-c", "ab"), Ordering::Less);
+        assert_eq!(collator.compare("a c", "ab"), Ordering::Greater);
+
+        collator.set_max_variable(MaxVariable::Symbol);
+        assert_eq!(collator.compare("a+c", "ab"), Ordering::Greater);
+        assert_eq!(collator.compare("a$c", "ab"), Ordering::Less);
+
+        collator.set_max_variable(MaxVariable::Currency);
+        assert_eq!(collator.compare("a$c", "ab"), Ordering::Greater);
+        assert_eq!(collator.compare("a1c", "ab"), Ordering::Less);
+    }
+
+    #[test]
+    fn total_cmp() {
+        let mut collator = Collator::default();
+        collator.set_strength(Strength::Primary);
+        let strings = ["a", "A", "\u{e1}", "a\u{301}", "a\u{0}", "b", ""];
+
+        for a in &strings {
+            for b in &strings {
+                let ordering = collator.total_cmp(a, b);
+                assert_eq!(ordering == Ordering::Equal, a == b, "{:?} {:?}", a, b);
+                assert_eq!(ordering, collator.total_cmp(b, a).reverse());
+            }
+        }
+        assert_eq!(collator.total_cmp("A", "b"), Ordering::Less);
+    }
+
+    #[test]
+    fn distinguishing_strength() {
+        let mut collator = Collator::default();
+        let strength = |c: &Collator, a, b| c.distinguishing_strength(a, b);
+
+        assert_eq!(strength(&collator, "a", "b"), Some(Strength::Primary));
+        assert_eq!(
+            strength(&collator, "a", "\u{e1}"),
+            Some(Strength::Secondary)
+        );
+        assert_eq!(strength(&collator, "a", "A"), Some(Strength::Tertiary));
+        assert_eq!(strength(&collator, "\u{e1}", "a\u{301}"), None);
+
+        collator.set_variable_weighting(VariableWeighting::Shifted);
+        assert_eq!(strength(&collator, "a-b", "ab"), Some(Strength::Quaternary));
+        // U+0000 is completely ignorable
+        assert_eq!(
+            strength(&collator, "a\u{0}", "a"),
+            Some(Strength::Identical)
+        );
+    }
+
+    #[test]
+    fn views() {
+        let collator = Collator::default().with_cache(8);
+        let primary = collator.with_strength(Strength::Primary);
+        let tertiary = collator.with_strength(Strength::Tertiary);
+
+        assert_eq!(primary.compare("a", "\u{c1}"), Ordering::Equal);
+        assert_eq!(tertiary.compare("a", "\u{c1}"), Ordering::Less);
+        assert_eq!(primary.sort_key("\u{c1}").secondary, []);
+        assert_eq!(tertiary.sort_key("\u{c1}").quaternary, []);
+        assert!(!tertiary.sort_key("\u{c1}").tertiary.is_empty());
+
+        // The collator itself is unchanged
+        assert_eq!(collator.strength, Strength::Quaternary);
+        assert!(!collator.sort_key("\u{c1}").secondary.is_empty());
+        assert_eq!(
+            collator
+                .with_strength(Strength::Quaternary)
+                .sort_key("\u{c1}"),
+            collator.sort_key("\u{c1}")
+        );
+    }
+
+    #[test]
+    fn primary_key() {
+        let mut collator = Collator::default();
+        let words = ["caf\u{e9}", "Cafe", "CAF\u{c9}", "cave", "ca-fe"];
+
+        let mut groups = std::collections::HashMap::new();
+        for word in &words {
+            assert_eq!(collator.primary_key(word), collator.sort_key(word).primary);
+            groups
+                .entry(collator.primary_key(word))
+                .or_insert_with(Vec::new)
+                .push(*word);
+        }
+        assert_eq!(groups[&collator.primary_key("cafe")], &words[..3]);
+        assert_eq!(groups.len(), 3);
+
+        // Shifted punctuation is not on the primary level
+        collator.set_variable_weighting(VariableWeighting::Shifted);
+        assert_eq!(collator.primary_key("ca-fe"), collator.primary_key("cafe"));
+        assert_eq!(
+            collator.primary_key("ca-fe"),
+            collator.sort_key("ca-fe").primary
+        );
+    }
+
+    #[test]
+    fn explain_sort() {
+        let collator = Collator::default();
+        let items = ["b", "\u{e1}", "A", "a", "ab"];
+        let explanation = collator.explain_sort(&items);
+
+        let mut sorted = items;
+        sorted.sort_by(|a, b| collator.compare(a, b));
+        let lines: Vec<_> = explanation.lines().collect();
+        assert_eq!(lines.len(), sorted.len());
+        for (line, item) in lines.iter().zip(&sorted) {
+            assert!(line.starts_with(&format!("{:?}", item)));
+            assert!(line.ends_with(&collator.sort_key(item).to_hex_string()));
+        }
+
+        // The weights are aligned
+        let a = collator.sort_key("a").primary[0];
+        assert_eq!(lines[0], format!("\"a\"   {:04X} | 0020 | 0002 |", a));
+        assert_eq!(collator.explain_sort(&[]), "");
+    }
+
+    #[test]
+    fn cmp_fn() {
+        let collator = Collator::default();
+        let mut v = ["cab", "b", "Cab", "a-b", "c\u{e1}b", "ab", "A"];
+        let mut expected = v;
+        expected.sort_by_cached_key(|s| collator.sort_key(s));
+
+        v.sort_unstable_by(collator.cmp_fn());
+        assert_eq!(v, expected);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn compare_os() {
+        use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+
+        let collator = Collator::default();
+        let os = |b: &[u8]| OsString::from_vec(b.to_vec());
+
+        let mut v = vec![
+            os(b"b"),
+            os(b"a\xff"),
+            os(b"\xff"),
+            os(b"A"),
+            os(b"a\xef\xbf\xbd"),
+            os(b"a\xfe"),
+            os(b"a"),
+        ];
+        v.sort_by(|a, b| collator.compare_os(a, b));
+        assert_eq!(
+            v,
+            [
+                os(b"a"),
+                os(b"A"),
+                os(b"a\xef\xbf\xbd"),
+                os(b"a\xfe"),
+                os(b"a\xff"),
+                os(b"b"),
+                os(b"\xff"),
+            ]
+        );
+
+        assert_eq!(
+            collator.compare_os(OsStr::new("Cargo.toml"), OsStr::new("build.rs")),
+            collator.compare("Cargo.toml", "build.rs")
+        );
+    }
+
+    #[test]
+    fn cache() {
+        let mut collator = Collator::default().with_cache(8);
+        let uncached = Collator::default();
+
+        let words = ["b", "A", "a-b", "ab", "c", "á", "a"];
+        for a in &words {
+            for b in &words {
+                assert_eq!(collator.compare(a, b), uncached.compare(a, b));
+            }
+        }
+        assert_eq!(
+            collator.cache.as_ref().unwrap().lock().unwrap().hits,
+            words.len() * words.len() * 2 - words.len()
+        );
+
+        // Changing an option invalidates the cached keys
+        collator.set_variable_weighting(VariableWeighting::Shifted);
+        assert_eq!(collator.compare("a-b", "ab"), Ordering::Less);
+        assert_eq!(
+            collator.sort_key("a-b").primary,
+            collator.sort_key("ab").primary
+        );
+    }
+
+    #[test]
+    fn strength() {
+        let mut collator = Collator::default();
+        collator.set_strength(Strength::Primary);
+        assert!(collator.is_equal("caf\u{e9}", "Cafe"));
+        assert_eq!(
+            collator.compare_tristate("caf\u{e9}", "cafe"),
+            Tristate::Equal
+        );
+        assert_eq!(
+            collator.compare_tristate("caf\u{e9}", "cafe\u{301}"),
+            Tristate::Identical
+        );
+        assert_eq!(
+            collator.compare_tristate("cafe", "cave"),
+            Tristate::Ordered(Ordering::Less)
+        );
+        assert!(collator.sort_key("caf\u{e9}").secondary.is_empty());
+
+        collator.set_strength(Strength::Secondary);
+        assert!(collator.is_equal("cafe", "Cafe"));
+        assert_eq!(collator.compare("cafe", "caf\u{e9}"), Ordering::Less);
+
+        collator.set_strength(Strength::Identical);
+        collator.set_tertiary_feature(TertiaryFeature::Width, false);
+        assert_eq!(collator.compare("cafe", "\u{ff43}afe"), Ordering::Less);
+        assert_eq!(
+            collator.compare("caf\u{e9}", "cafe\u{301}"),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn tertiary_features() {
+        let mut collator = Collator::default();
+        collator.set_tertiary_feature(TertiaryFeature::Case, false);
+        assert_eq!(collator.compare("a", "A"), Ordering::Equal);
+        assert_eq!(collator.compare("\u{ff41}", "\u{ff21}"), Ordering::Equal);
+        assert_eq!(collator.compare("A", "\u{ff21}"), Ordering::Less);
+        assert_eq!(collator.compare("a", "\u{e1}"), Ordering::Less);
+        assert_eq!(collator.compare("\u{3042}", "\u{30a2}"), Ordering::Less);
+
+        collator.set_tertiary_feature(TertiaryFeature::Width, false);
+        collator.set_tertiary_feature(TertiaryFeature::Kana, false);
+        assert_eq!(collator.compare("A", "\u{ff21}"), Ordering::Equal);
+        assert_eq!(collator.compare("\u{3042}", "\u{ff71}"), Ordering::Equal);
+        assert_eq!(collator.compare("a", "\u{24d0}"), Ordering::Less);
+
+        collator.set_tertiary_feature(TertiaryFeature::CompatVariant, false);
+        assert_eq!(collator.compare("a", "\u{24d0}"), Ordering::Equal);
+
+        collator.set_tertiary_feature(TertiaryFeature::Case, true);
+        assert_eq!(collator.compare("a", "A"), Ordering::Less);
+    }
+
+    #[test]
+    fn full_and_half_width() {
+        let mut collator = Collator::default();
+        let full = "\u{ff21}\u{ff22}\u{ff23}";
+        let half_katakana = "\u{ff71}\u{ff72}";
+        let katakana = "\u{30a2}\u{30a4}";
+
+        // The forms only differ on the tertiary level
+        assert_eq!(
+            collator.distinguishing_strength("ABC", full),
+            Some(Strength::Tertiary)
+        );
+        assert_eq!(
+            collator.distinguishing_strength(katakana, half_katakana),
+            Some(Strength::Tertiary)
+        );
+        assert_eq!(
+            collator
+                .with_strength(Strength::Secondary)
+                .compare("ABC", full),
+            Ordering::Equal
+        );
+
+        collator.set_tertiary_feature(TertiaryFeature::Width, false);
+        assert_eq!(collator.compare("ABC", full), Ordering::Equal);
+        assert_eq!(collator.compare(katakana, half_katakana), Ordering::Equal);
+        assert_eq!(collator.compare("abc", full), Ordering::Less);
+    }
+
+    #[test]
+    fn for_locale() {
+        let sorted = |id, mut v: Vec<&'static str>| {
+            let collator = Collator::for_locale(id).unwrap();
+            v.sort_by(|a, b| collator.compare(a, b));
+            v
+        };
+        let words = vec!["af", "\u{e4}", "ae", "ad", "b"];
+        assert_eq!(
+            sorted("de-DE-u-co-phonebk", words.clone()),
+            ["ad", "ae", "\u{e4}", "af", "b"]
+        );
+        assert_eq!(sorted("de-DE", words), ["\u{e4}", "ad", "ae", "af", "b"]);
+
+        let words = vec!["d", "ch", "cz", "\u{f1}", "o"];
+        assert_eq!(
+            sorted("es", words.clone()),
+            ["ch", "cz", "d", "\u{f1}", "o"]
+        );
+        assert_eq!(
+            sorted("es-u-co-trad", words),
+            ["cz", "ch", "d", "\u{f1}", "o"]
+        );
+
+        assert!(matches!(
+            Collator::for_locale("not a locale"),
+            Err(Error::Locale(_))
+        ));
+    }
+
+    #[test]
+    fn locale_tables_are_shared() {
+        let table = |id| match Collator::for_locale(id).unwrap().table {
+            Cow::Borrowed(table) => table as *const CollationElementTable,
+            Cow::Owned(_) => panic!("{} has a table of its own", id),
+        };
+        let root = root_table() as *const _;
+        assert_eq!(table("es-ES"), table("es"));
+        assert_eq!(table("es-u-co-trad"), table("es-MX-u-co-trad"));
+        assert_ne!(table("es"), table("es-u-co-trad"));
+        assert_ne!(table("es"), root);
+        // Unknown collation types fall back to the default one
+        assert_eq!(table("es-u-co-xyzzy"), table("es"));
+        // Locales without bundled rules use the root table
+        assert_eq!(table("fr"), root);
+        assert_eq!(table("de"), root);
+    }
+
+    #[test]
+    fn order_report() {
+        let report = Collator::default().order_report(vec!['b', '\u{e1}', 'A', 'a']);
+        let chars: Vec<_> = report.iter().map(|(c, _)| *c).collect();
+        assert_eq!(chars, ['a', 'A', '\u{e1}', 'b']);
+        assert!(report.windows(2).all(|w| w[0].1 <= w[1].1));
+    }
+
+    #[test]
+    fn with_tailoring() {
+        let collator =
+            Collator::with_tailoring(CollationElementTable::default(), "&a < x < y").unwrap();
+        let mut v = ["y", "b", "x", "\u{e1}", "ab", "A", "a"];
+        v.sort_by(|a, b| collator.compare(a, b));
+        assert_eq!(v, ["a", "A", "\u{e1}", "ab", "x", "y", "b"]);
+    }
+
+    #[test]
+    fn bucket_labels() {
+        let collator = Collator::default();
+        assert_eq!(collator.bucket_labels(), ('A'..='Z').collect::<Vec<_>>());
+
+        let bucket = |s| collator.first_primary_label(s);
+        assert_eq!(bucket("apple"), Some('A'));
+        assert_eq!(bucket("\u{c1}pple"), Some('A'));
+        assert_eq!(bucket("banana"), Some('B'));
+        assert_eq!(bucket("\u{141}\u{f3}d\u{17a}"), Some('L'));
+        assert_eq!(bucket("\u{17e}ebra"), Some('Z'));
+        assert_eq!(bucket("\u{fe}orn"), Some('Z'));
+
+        // Digits sort before the first label and Greek after the last one
+        assert_eq!(bucket("123"), None);
+        assert_eq!(bucket("\u{3a9}mega"), None);
+        assert_eq!(bucket(""), None);
+    }
+
+    #[test]
+    fn hangul_bucket_labels() {
+        let mut collator = Collator::default();
+        collator.set_index_script(IndexScript::Hangul);
+        assert_eq!(collator.bucket_labels().len(), 14);
+
+        let bucket = |s| collator.first_primary_label(s);
+        assert_eq!(bucket("\u{ac00}"), Some('\u{3131}'));
+        assert_eq!(bucket("\u{ae4c}"), Some('\u{3131}'));
+        assert_eq!(bucket("\u{b098}"), Some('\u{3134}'));
+        assert_eq!(bucket("\u{d558}\u{b098}"), Some('\u{314e}'));
+        // Conjoining jamo and compatibility jamo
+        assert_eq!(bucket("\u{1102}\u{1161}"), Some('\u{3134}'));
+        assert_eq!(bucket("\u{3137}"), Some('\u{3137}'));
+
+        assert_eq!(bucket("apple"), None);
+        assert_eq!(bucket("\u{3042}"), None);
+    }
+
+    #[test]
+    fn ignored_chars() {
+        let mut collator = Collator::default();
+        assert_ne!(collator.compare("O'Brien", "OBrien"), Ordering::Equal);
+
+        collator.set_ignored_chars(['\'', '-']);
+        assert_eq!(collator.compare("O'Brien", "OBrien"), Ordering::Equal);
+        assert_eq!(
+            collator.compare("Smith-Jones", "SmithJones"),
+            Ordering::Equal
+        );
+        assert_eq!(collator.sort_key("-"), collator.sort_key(""));
+        assert_eq!(collator.first_primary_label("'t Hooft"), Some('T'));
+
+        // Even when variable characters are shifted, they would still count
+        // on the quaternary level
+        collator.set_variable_weighting(VariableWeighting::Shifted);
+        collator.set_strength(Strength::Quaternary);
+        assert_eq!(collator.compare("O'Brien", "OBrien"), Ordering::Equal);
+        assert_eq!(collator.compare("O Brien", "OBrien"), Ordering::Less);
+
+        let mut names = ["O'Neill", "Obama", "OBrien", "O'Brien", "Ohlsson", "O'Hara"];
+        names.sort_by(collator.cmp_fn());
+        assert_eq!(
+            names,
+            ["Obama", "OBrien", "O'Brien", "O'Hara", "Ohlsson", "O'Neill"]
+        );
+
+        collator.set_ignored_chars([]);
+        assert_ne!(collator.compare("O'Brien", "OBrien"), Ordering::Equal);
+    }
+
+    #[test]
+    fn collated_strings() {
+        let collator = Collator::default();
+        let names: BTreeSet<_> = ["Zoë", "émile", "Zoe", "Emile", "zoe", "adam"]
+            .iter()
+            .map(|&name| collator.collated(name))
+            .collect();
+        let names: Vec<_> = names.iter().map(CollatedString::as_str).collect();
+        assert_eq!(names, ["adam", "Emile", "émile", "zoe", "Zoe", "Zoë"]);
+
+        let mut ages = BTreeMap::new();
+        ages.insert(collator.collated("b"), 2);
+        ages.insert(collator.collated("A"), 1);
+        ages.insert(collator.collated("b"), 3);
+        assert_eq!(ages.len(), 2);
+        assert_eq!(ages.get(&collator.collated("b")), Some(&3));
+        assert_eq!(ages.keys().next().unwrap().to_string(), "A");
+    }
+
+    #[test]
+    fn graphemes() {
+        let collator = Collator::default();
+        let cafe = ["c", "a", "f", "e"];
+        assert_eq!(
+            collator.compare_graphemes(&["c", "a", "f", "\u{e9}"], &cafe),
+            Ordering::Greater
+        );
+        assert_eq!(
+            collator.compare_graphemes(&["c", "a", "f", "e\u{301}"], &["c", "a", "f", "\u{e9}"]),
+            Ordering::Equal
+        );
+        assert_eq!(
+            collator.compare_graphemes(&["ca", "fe"], &cafe),
+            Ordering::Equal
+        );
+        assert_eq!(collator.compare_graphemes(&[], &cafe), Ordering::Less);
+
+        // A contraction spanning a cluster boundary
+        let collator =
+            Collator::with_tailoring(CollationElementTable::default(), "&z < ch").unwrap();
+        assert_eq!(
+            collator.compare_graphemes(&["c", "h"], &["z"]),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn compatibility_decomposition() {
+        let mut collator = Collator::default();
+        let pairs = [
+            ("\u{fb01}", "fi"),
+            ("\u{2460}", "1"),
+            ("x\u{b2}", "x2"),
+            ("\u{ff21}\u{ff22}", "AB"),
+        ];
+        for &(a, b) in &pairs {
+            assert_eq!(
+                collator.distinguishing_strength(a, b),
+                Some(Strength::Tertiary)
+            );
+        }
+
+        collator.set_decomposition(Decomposition::Compatibility);
+        for &(a, b) in &pairs {
+            assert_eq!(collator.compare(a, b), Ordering::Equal);
+            assert_eq!(collator.primary_key(a), collator.primary_key(b));
+        }
+        // Still canonical equivalence and case count
+        assert_eq!(collator.compare("\u{e9}", "e\u{301}"), Ordering::Equal);
+        assert_eq!(collator.compare("\u{fb01}", "FI"), Ordering::Less);
+
+        collator.set_strength(Strength::Primary);
+        assert_eq!(collator.compare("\u{fb01}", "fi"), Ordering::Equal);
+    }
+
+    #[test]
+    fn reordering() {
+        let words = [
+            "zeta",
+            "\u{3b6}\u{3ae}\u{3c4}\u{3b1}",
+            "alpha",
+            "\u{430}",
+            "1",
+            "\u{5d0}",
+            "-",
+        ];
+        let sorted = |collator: &Collator| {
+            let mut v = words;
+            v.sort_by(collator.cmp_fn());
+            v
+        };
+        let mut collator = Collator::default();
+        let default = sorted(&collator);
+        assert_eq!(
+            default,
+            [
+                "-",
+                "1",
+                "alpha",
+                "zeta",
+                "\u{3b6}\u{3ae}\u{3c4}\u{3b1}",
+                "\u{430}",
+                "\u{5d0}"
+            ]
+        );
+
+        // Latin is already first, and the rest keeps its order
+        collator.set_reordering(&["Latn", "others"]).unwrap();
+        assert_eq!(sorted(&collator), default);
+
+        collator.set_reordering(&["Grek", "others"]).unwrap();
+        assert_eq!(
+            sorted(&collator),
+            [
+                "-",
+                "1",
+                "\u{3b6}\u{3ae}\u{3c4}\u{3b1}",
+                "alpha",
+                "zeta",
+                "\u{430}",
+                "\u{5d0}"
+            ]
+        );
+
+        collator
+            .set_reordering(&["Hebr", "others", "Latn"])
+            .unwrap();
+        assert_eq!(
+            sorted(&collator),
+            [
+                "-",
+                "1",
+                "\u{5d0}",
+                "\u{3b6}\u{3ae}\u{3c4}\u{3b1}",
+                "\u{430}",
+                "alpha",
+                "zeta"
+            ]
+        );
+        assert_eq!(collator.first_primary_label("zeta"), Some('Z'));
+
+        collator.set_reordering(&[]).unwrap();
+        assert_eq!(sorted(&collator), default);
+
+        assert_eq!(
+            collator.set_reordering(&["Grek", "Qaaa"]),
+            Err(TailoringError::UnknownScript("Qaaa".into()))
+        );
+
+        let collator = Collator::with_tailoring(
+            CollationElementTable::default(),
+            "[reorder Cyrl others]&a<b",
+        )
+        .unwrap();
+        assert_eq!(collator.compare("\u{430}", "a"), Ordering::Less);
+    }
+
+    #[test]
+    fn case_insensitive_accent_sensitive() {
+        let collator = Collator::default().case_insensitive_accent_sensitive();
+        assert!(collator.is_equal("R\u{e9}sum\u{e9}", "r\u{e9}sum\u{e9}"));
+        assert!(collator.is_equal("RESUME", "resume"));
+        assert!(!collator.is_equal("Resume", "R\u{e9}sum\u{e9}"));
+        assert_eq!(
+            collator.compare("resume", "R\u{e9}sum\u{e9}"),
+            Ordering::Less
+        );
+        // Unlike at secondary strength, width still counts
+        assert!(!collator.is_equal("\u{ff21}", "a"));
+    }
+
+    #[test]
+    fn sort_by() {
+        struct Person {
+            name: String,
+            age: u32,
+        }
+        let person = |name: &str, age| Person {
+            name: name.into(),
+            age,
+        };
+        let mut people = vec![
+            person("Zo\u{eb}", 31),
+            person("\u{c9}mile", 40),
+            person("adam", 25),
+            person("\u{d6}zil", 52),
+            person("Adam", 60),
+            person("emile", 18),
+        ];
+        let collator = Collator::default();
+        collator.sort_by(&mut people, |p| &p.name);
+        let names: Vec<_> = people.iter().map(|p| &p.name[..]).collect();
+        assert_eq!(
+            names,
+            [
+                "adam",
+                "Adam",
+                "emile",
+                "\u{c9}mile",
+                "\u{d6}zil",
+                "Zo\u{eb}"
+            ]
+        );
+        assert_eq!(people[0].age, 25);
+
+        // Stable for equal names
+        let mut collator = Collator::default();
+        collator.set_strength(Strength::Primary);
+        collator.sort_by(&mut people, |p| &p.name);
+        let ages: Vec<_> = people.iter().map(|p| p.age).collect();
+        assert_eq!(ages, [25, 60, 18, 40, 52, 31]);
+    }
+
+    #[test]
+    fn search_by_sort_key() {
+        let collator = Collator::default();
+        let mut entries: Vec<_> = ["Zo\u{eb}", "\u{c9}mile", "adam", "\u{d6}zil", "bob"]
+            .iter()
+            .map(|&name| (name.to_owned(), collator.sort_key(name)))
+            .collect();
+        entries.sort_by(|a, b| a.1.cmp(&b.1));
+
+        assert_eq!(
+            collator.compare_to_key("adam", &entries[0].1),
+            Ordering::Equal
+        );
+        assert_eq!(
+            collator.compare_to_key("Adam", &entries[0].1),
+            Ordering::Greater
+        );
+        assert_eq!(
+            collator.compare_to_key("Adam", &entries[1].1),
+            Ordering::Less
+        );
+
+        let search = |s| collator.binary_search_by_sort_key(&entries, s, |(_, key)| key);
+        assert_eq!(search("\u{d6}zil"), Ok(3));
+        assert_eq!(search("O\u{308}zil"), Ok(3));
+        assert_eq!(search("emile"), Err(2));
+        assert_eq!(search("Zoe"), Err(4));
+        assert_eq!(search("zz"), Err(5));
+    }
+
+    #[test]
+    fn merge_sorted() {
+        let collator = Collator::default();
+        let sorted = |words: &[&str]| {
+            let mut words: Vec<String> = words.iter().map(|&w| w.into()).collect();
+            words.sort_by(|a, b| collator.compare(a, b));
+            words
+        };
+        let inputs = vec![
+            sorted(&["peach", "Apple", "\u{e9}clair", "zucchini"]),
+            sorted(&["apple", "banana", "Eclair"]),
+            vec![],
+            sorted(&["cherry", "APPLE", "yam", "date"]),
+        ];
+        let all: Vec<_> = inputs.iter().flatten().map(|s| &s[..]).collect();
+        let merged: Vec<_> = collator.merge_sorted(inputs.clone()).collect();
+        assert_eq!(merged, sorted(&all));
+        assert_eq!(
+            merged,
+            [
+                "apple",
+                "Apple",
+                "APPLE",
+                "banana",
+                "cherry",
+                "date",
+                "Eclair",
+                "\u{e9}clair",
+                "peach",
+                "yam",
+                "zucchini"
+            ]
+        );
+
+        // Equal strings come in the order of the inputs
+        let mut collator = Collator::default();
+        collator.set_strength(Strength::Primary);
+        let merged: Vec<_> = collator.merge_sorted(inputs).collect();
+        assert_eq!(&merged[..3], ["Apple", "apple", "APPLE"]);
+        assert_eq!(collator.merge_sorted(Vec::<Vec<String>>::new()).count(), 0);
+    }
+
+    #[test]
+    fn partition_equal() {
+        let mut items = [
+            "resume",
+            "R\u{e9}sum\u{e9}",
+            "RESUME",
+            "r\u{e9}sum\u{e9}",
+            "Resume",
+            "re\u{301}sume\u{301}",
+        ];
+        let mut collator = Collator::default();
+        collator.set_strength(Strength::Secondary);
+        items.sort_by(collator.cmp_fn());
+        let classes: Vec<_> = collator.partition_equal(&items).collect();
+        assert_eq!(
+            classes,
+            [
+                &["resume", "RESUME", "Resume"][..],
+                &[
+                    "R\u{e9}sum\u{e9}",
+                    "r\u{e9}sum\u{e9}",
+                    "re\u{301}sume\u{301}"
+                ],
+            ]
+        );
+
+        // Only neighbours are grouped
+        let items = ["a", "b", "A"];
+        assert_eq!(collator.partition_equal(&items).count(), 3);
+        assert_eq!(collator.partition_equal::<&str>(&[]).count(), 0);
+    }
+
+    #[test]
+    fn common_primary_prefix_len() {
+        let collator = Collator::default();
+        assert_eq!(collator.common_primary_prefix_len("apple", "apples"), 5);
+        assert_eq!(collator.common_primary_prefix_len("apple", "banana"), 0);
+        assert_eq!(collator.common_primary_prefix_len("apple", "Apple"), 5);
+        assert_eq!(
+            collator.common_primary_prefix_len("r\u{e9}sum\u{e9}", "resumes"),
+            6
+        );
+        assert_eq!(collator.common_primary_prefix_len("", "a"), 0);
+        // Ligatures and expansions count their elements
+        assert_eq!(collator.common_primary_prefix_len("\u{e6}", "ae"), 2);
+    }
+
+    #[test]
+    fn hiragana_quaternary() {
+        let mut collator = Collator::default();
+        assert_eq!(
+            collator.distinguishing_strength("\u{3042}", "\u{30a2}"),
+            Some(Strength::Tertiary)
+        );
+
+        for &weighting in &[VariableWeighting::NonIgnorable, VariableWeighting::Shifted] {
+            collator.set_variable_weighting(weighting);
+            collator.set_hiragana_quaternary(true);
+            collator.set_strength(Strength::Quaternary);
+            assert_eq!(
+                collator.distinguishing_strength("\u{3042}", "\u{30a2}"),
+                Some(Strength::Quaternary)
+            );
+            assert_eq!(collator.compare("\u{3042}", "\u{30a2}"), Ordering::Less);
+            assert_eq!(collator.compare("\u{3041}", "\u{30a1}"), Ordering::Less);
+            assert_eq!(
+                collator.compare("\u{3042}\u{304b}", "\u{3042}\u{30ab}"),
+                Ordering::Less
+            );
+            // Other tertiary differences stay where they are
+            assert_eq!(
+                collator.distinguishing_strength("\u{3042}", "\u{3041}"),
+                Some(Strength::Tertiary)
+            );
+            assert_eq!(collator.compare("a", "\u{3042}"), Ordering::Less);
+
+            collator.set_strength(Strength::Tertiary);
+            assert!(collator.is_equal("\u{3042}", "\u{30a2}"));
+        }
+
+        let collator =
+            Collator::with_tailoring(CollationElementTable::default(), "[hiraganaQ on]&a<b")
+                .unwrap();
+        assert_eq!(
+            collator.distinguishing_strength("\u{3042}", "\u{30a2}"),
+            Some(Strength::Quaternary)
+        );
+
+        // Tailoring moves the tertiary weights, but not which are hiragana
+        let collator =
+            Collator::with_tailoring(CollationElementTable::default(), "[hiraganaQ on]&a<<<x")
+                .unwrap();
+        assert_eq!(
+            collator.distinguishing_strength("\u{3042}", "\u{30a2}"),
+            Some(Strength::Quaternary)
+        );
+        assert_eq!(collator.compare("\u{3041}", "\u{30a1}"), Ordering::Less);
+    }
+
+    #[test]
+    fn tailoring_warnings() {
+        let tailored = |rules| {
+            Collator::with_tailoring_warnings(CollationElementTable::default(), rules).unwrap()
+        };
+        let (collator, warnings) = tailored("[strength 1] &a <<< x <<<* yz");
+        assert_eq!(collator.strength, Strength::Primary);
+        assert_eq!(
+            warnings,
+            [
+                BuildWarning {
+                    sequence: "x".into(),
+                    strength: Strength::Tertiary
+                },
+                BuildWarning {
+                    sequence: "yz".into(),
+                    strength: Strength::Tertiary
+                },
+            ]
+        );
+        assert!(collator.is_equal("a", "x"));
+        assert_eq!(
+            warnings[0].to_string(),
+            "\"x\" needs strength Tertiary, but the collator has a lower one"
+        );
+
+        let (_, warnings) = tailored("[strength 2] &a < x << y <<< z");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].sequence, "z");
+        let (_, warnings) = tailored("&a <<< x");
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn case_level_and_case_first() {
+        let tailored = |rules| {
+            Collator::with_tailoring_warnings(CollationElementTable::default(), rules).unwrap()
+        };
+
+        // Case still counts, but the other tertiary differences don't
+        let (collator, warnings) = tailored("[caseLevel on][strength 2]");
+        assert!(warnings.is_empty());
+        let key = collator.sort_key("a");
+        assert_eq!(key.case, [FIRST_CASE]);
+        assert!(key.tertiary.is_empty());
+        assert_eq!(
+            key.compare_detailed(&collator.sort_key("A")),
+            (Ordering::Less, Some(Level::Case))
+        );
+        assert!(key.to_bytes() < collator.sort_key("A").to_bytes());
+        assert_eq!(collator.compare("\u{e1}", "A"), Ordering::Greater);
+        assert!(collator.is_equal("a", "\u{ff41}"));
+        assert_eq!(
+            collator.distinguishing_strength("a", "A"),
+            Some(Strength::Primary)
+        );
+
+        // Ignoring accents but not case
+        let mut collator = Collator::default();
+        collator.set_strength(Strength::Primary);
+        collator.set_case_level(true);
+        collator.set_case_first(CaseFirst::Upper);
+        let mut v = ["b", "a", "B", "\u{c1}", "\u{e1}"];
+        v.sort_by(collator.cmp_fn());
+        assert_eq!(v, ["\u{c1}", "a", "\u{e1}", "B", "b"]);
+
+        // Without a case level, case comes first on the tertiary level
+        let mut collator = Collator::default();
+        collator.set_case_first(CaseFirst::Upper);
+        let mut v = ["ab", "Ab", "aB", "AB"];
+        v.sort_by(collator.cmp_fn());
+        assert_eq!(v, ["AB", "Ab", "aB", "ab"]);
+        assert_eq!(collator.compare("\u{24b6}", "a"), Ordering::Less);
+        collator.set_case_first(CaseFirst::Lower);
+        assert_eq!(collator.compare("a", "A"), Ordering::Less);
+        assert_eq!(collator.compare("\u{24b6}", "a"), Ordering::Greater);
+
+        // So it has no effect below the tertiary strength
+        let (collator, warnings) = tailored("[caseFirst upper][strength 2]");
+        assert!(collator.is_equal("a", "A"));
+        assert_eq!(
+            warnings,
+            [BuildWarning {
+                sequence: "[caseFirst upper]".into(),
+                strength: Strength::Tertiary
+            }]
+        );
+        let (collator, warnings) = tailored("[caseLevel on][caseFirst upper][strength 1]");
+        assert!(warnings.is_empty());
+        assert_eq!(collator.compare("A", "a"), Ordering::Less);
+
+        // The case of an element doesn't change when tailoring moves its
+        // tertiary weight
+        let (collator, _) = tailored("[caseFirst upper]&a<<<x");
+        for (upper, lower) in [
+            ("A", "a"),
+            ("\u{24b6}", "\u{24d0}"),
+            ("\u{ff21}", "\u{ff41}"),
+        ] {
+            assert_eq!(collator.compare(upper, lower), Ordering::Less);
+        }
+    }
+
+    #[test]
+    fn case_first_after_tailoring() {
+        let tailored =
+            |rules| Collator::with_tailoring(CollationElementTable::default(), rules).unwrap();
+        let sorted = |collator: &Collator, v: &[&'static str]| {
+            let mut v = v.to_vec();
+            v.sort_by(collator.cmp_fn());
+            v
+        };
+
+        // Tertiary relations move the tertiary weights of the whole table,
+        // but every element keeps its case
+        let collator = tailored("[caseFirst upper]&a<<<x<<<y<<<z<<<w<<<v");
+        for (upper, lower) in [
+            ("A", "a"),
+            ("\u{24b6}", "\u{24d0}"),
+            ("\u{ff21}", "\u{ff41}"),
+        ] {
+            assert_eq!(collator.compare(upper, lower), Ordering::Less);
+        }
+        assert_eq!(
+            sorted(&collator, &["v", "a", "x", "A", "b"]),
+            ["A", "a", "x", "v", "b"]
+        );
+
+        // Tailored sequences get the case of their own characters
+        let collator = tailored(
+            "[caseFirst upper]&D<<\u{111}<<<\u{110}\
+             &[before 1]\u{1c0}<\u{e6}<<<\u{c6}<\u{e5}<<<\u{c5}<<<aa<<<Aa<<<AA",
+        );
+        for (upper, lower) in [
+            ("A", "a"),
+            ("\u{24b6}", "\u{24d0}"),
+            ("\u{ff21}", "\u{ff41}"),
+            ("\u{110}", "\u{111}"),
+            ("\u{c6}", "\u{e6}"),
+            ("\u{c5}", "\u{e5}"),
+        ] {
+            assert_eq!(collator.compare(upper, lower), Ordering::Less);
+        }
+        assert_eq!(
+            sorted(&collator, &["aa", "\u{e5}", "AA", "\u{c5}", "z", "\u{e6}"]),
+            ["z", "\u{e6}", "\u{c5}", "AA", "\u{e5}", "aa"]
+        );
+
+        // Without case and width differences, the moved weights still match
+        let mut collator = tailored("&a<<<x<<<y");
+        collator.set_case_level(true);
+        assert_eq!(
+            collator.distinguishing_strength("a", "A"),
+            Some(Strength::Primary)
+        );
+        assert!(collator.sort_key("A").tertiary == collator.sort_key("a").tertiary);
+        collator.set_tertiary_feature(TertiaryFeature::Width, false);
+        assert!(collator.sort_key("\u{ff21}").tertiary == collator.sort_key("a").tertiary);
+    }
+
+    #[test]
+    fn root() {
+        let a = Collator::root();
+        let b = Collator::root();
+        // Both borrow the same table instead of parsing their own
+        assert!(matches!(
+            (&a.table, &b.table),
+            (Cow::Borrowed(a), Cow::Borrowed(b)) if std::ptr::eq(*a, *b)
+        ));
+        assert!(matches!(Collator::default().table, Cow::Borrowed(_)));
+        assert_eq!(a.compare("a", "B"), Ordering::Less);
+
+        // Changing the options of one doesn't affect the other
+        let mut a = a;
+        a.set_strength(Strength::Primary);
+        assert!(a.is_equal("a", "A"));
+        assert!(!b.is_equal("a", "A"));
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| std::thread::spawn(|| Collator::root().compare("b", "a")))
+            .collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), Ordering::Greater);
+        }
+    }
+
+    #[test]
+    fn fold_to_base() {
+        // Danish sorts ø as a separate letter after z
+        let mut collator =
+            Collator::with_tailoring(CollationElementTable::default(), "&z < \u{f8} <<< \u{d8}")
+                .unwrap();
+        collator.set_strength(Strength::Primary);
+        assert!(!collator.is_equal("S\u{f8}ren", "Soren"));
+
+        collator.set_fold_to_base(true);
+        assert!(collator.is_equal("S\u{f8}ren", "Soren"));
+        assert!(collator.is_equal("S\u{d8}REN", "soren"));
+        assert!(collator.is_equal("\u{141}\u{f3}d\u{17a}", "Lodz"));
+        assert!(collator.is_equal("Stra\u{df}e", "Strasse"));
+        assert!(collator.is_equal("\u{c6}sop", "AEsop"));
+        // Marks that are not accents are kept
+        assert!(!collator.is_equal("\u{915}\u{93f}", "\u{915}"));
+
+        collator.set_fold_to_base(false);
+        assert!(!collator.is_equal("S\u{f8}ren", "Soren"));
+    }
+
+    #[test]
+    fn dedup() {
+        let items = || vec!["caf\u{e9}".to_string(), "cafe".into(), "CAFE".into()];
+        let mut collator = Collator::default();
+        let mut v = items();
+        collator.dedup(&mut v);
+        assert_eq!(v, ["cafe", "CAFE", "caf\u{e9}"]);
+
+        collator.set_strength(Strength::Primary);
+        let mut v = items();
+        collator.dedup(&mut v);
+        assert_eq!(v, ["caf\u{e9}"]);
+
+        // Without sorting, only neighbours are removed
+        let mut v = vec!["a", "b", "A", "B", "b"];
+        collator.dedup_by_collation(&mut v);
+        assert_eq!(v, ["a", "b", "A", "B"]);
+    }
+
+    #[test]
+    fn versions() {
+        let collator = Collator::default();
+        assert_eq!(collator.uca_version(), "15.1.0");
+        let version = collator.data_version().unwrap();
+        assert!(!version.is_empty());
+        assert!(version.split('.').all(|n| n.parse::<u8>().is_ok()));
+
+        let table = CollationElementTable::from("0061 ; [.1FA2.0020.0002]\n").unwrap();
+        assert_eq!(Collator::new(table).data_version(), None);
+    }
+
+    #[test]
+    fn variable_top_of_groups() {
+        let table = CollationElementTable::default();
+        let tops: Vec<_> = [
+            MaxVariable::Space,
+            MaxVariable::Punct,
+            MaxVariable::Symbol,
+            MaxVariable::Currency,
+        ]
+        .iter()
+        .map(|&m| variable_top(&table, m))
+        .collect();
+
+        assert!(tops.windows(2).all(|w| w[0] < w[1]));
+        assert!(table["a"][0].primary > tops[3]);
+
+        // Without any symbols the markers of the table are used
+        let table =
+            CollationElementTable::from("002D ; [*0201.0020.0002]\n0061 ; [.0202.0020.0002]\n")
+                .unwrap();
+        assert_eq!(variable_top(&table, MaxVariable::Punct), 0x0201);
+    }
+
+    #[test]
+    fn numeric_ordering() {
+        let mut collator = Collator::default();
+        assert_eq!(collator.compare("page2", "page10"), Ordering::Greater);
+        collator.set_numeric(true);
+
+        let sorted = |c: &Collator, words: &[&'static str]| {
+            let mut words = words.to_vec();
+            c.sort_by(&mut words, |w| w);
+            words
+        };
+        assert_eq!(
+            sorted(&collator, &["page10", "page2", "page1", "page100"]),
+            ["page1", "page2", "page10", "page100"]
+        );
+        // Arabic-Indic, Devanagari and ASCII digits by their value
+        assert_eq!(
+            collator.compare("page\u{662}", "page\u{661}\u{660}"),
+            Ordering::Less
+        );
+        assert_eq!(
+            collator.compare("\u{969}", "\u{967}\u{968}"),
+            Ordering::Less
+        );
+        assert_eq!(
+            sorted(&collator, &["\u{661}\u{660}", "9", "\u{967}1", "\u{666}"]),
+            ["\u{666}", "9", "\u{661}\u{660}", "\u{967}1"]
+        );
+        // A number can mix scripts, which only makes a difference at the
+        // identical level
+        assert_eq!(collator.compare("1\u{662}", "\u{661}2"), Ordering::Equal);
+        let identical = collator.with_strength(Strength::Identical);
+        assert_eq!(identical.compare("\u{662}3", "23"), Ordering::Greater);
+        // Leading zeros are ignored
+        assert_eq!(collator.compare("007", "7"), Ordering::Equal);
+        assert_eq!(collator.compare("0", "000"), Ordering::Equal);
+        assert_eq!(collator.compare("08", "10"), Ordering::Less);
+        // Long numbers still sort by length first
+        assert_eq!(
+            collator.compare("99999999999", "100000000000"),
+            Ordering::Less
+        );
+
+        // A mark after a digit stays with it, below the primary level
+        assert_eq!(
+            collator.sort_key("12\u{301}").primary,
+            collator.sort_key("12").primary
+        );
+        assert_eq!(collator.compare("12\u{301}", "12"), Ordering::Greater);
+        assert_eq!(collator.compare("12\u{301}", "13"), Ordering::Less);
+
+        // Contractions are still matched across the start of a number
+        let collator = Collator::with_tailoring(
+            CollationElementTable::default(),
+            "[numericOrdering on]&z < a1",
+        )
+        .unwrap();
+        assert_eq!(collator.compare("a9", "a10"), Ordering::Less);
+        assert_eq!(collator.compare("a12", "z"), Ordering::Greater);
+        assert_eq!(collator.compare("a12", "a13"), Ordering::Less);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{Level, Strength};
+use std::{error, fmt, io};
+
+/// Errors that can occur while loading collation data.
+#[derive(Debug)]
+pub enum Error {
+    /// The data could not be read.
+    Io(io::Error),
+    /// A collation element table could not be parsed, starting at `line`.
+    Table { line: usize },
+    /// Tailoring rules could not be parsed.
+    Rules,
+    /// An LDML document could not be parsed.
+    Xml,
+    /// A locale identifier is not a valid BCP 47 language tag.
+    Locale(String),
+    /// The tailoring of a locale could not be applied.
+    Tailoring(TailoringError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "could not read collation data: {}", e),
+            Error::Table { line } => {
+                write!(f, "invalid collation element table at line {}", line)
+            }
+            Error::Rules => write!(f, "invalid collation rules"),
+            Error::Xml => write!(f, "invalid LDML document"),
+            Error::Locale(id) => write!(f, "invalid locale identifier {:?}", id),
+            Error::Tailoring(e) => write!(f, "could not apply tailoring: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Tailoring(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<TailoringError> for Error {
+    fn from(e: TailoringError) -> Self {
+        Error::Tailoring(e)
+    }
+}
+
+/// Errors that can occur while applying tailoring rules to a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TailoringError {
+    /// The rules could not be parsed.
+    Syntax,
+    /// A reset refers to a sequence that is not in the table.
+    AnchorNotFound(String),
+    /// A reset is placed before a level other than 1, 2 or 3.
+    InvalidBeforeLevel(u8),
+    /// The extension (after `/`) of a relation is not in the table.
+    ExpansionTargetMissing(String),
+    /// A reordering lists a script code that is not known.
+    UnknownScript(String),
+    /// There is no room for another weight at `level`, because the weights
+    /// above it can't be moved up any further.
+    WeightOverflow { level: Level },
+    /// A setting has a value it can't take, like `[caseFirst foo]`.
+    InvalidSetting { key: String, value: String },
+}
+
+impl fmt::Display for TailoringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TailoringError::Syntax => write!(f, "invalid collation rules"),
+            TailoringError::AnchorNotFound(s) => write!(f, "reset to unknown sequence {:?}", s),
+            TailoringError::InvalidBeforeLevel(level) => {
+                write!(f, "invalid level {} for [before]", level)
+            }
+            TailoringError::ExpansionTargetMissing(s) => {
+                write!(f, "extension to unknown sequence {:?}", s)
+            }
+            TailoringError::UnknownScript(code) => write!(f, "unknown script code {:?}", code),
+            TailoringError::WeightOverflow { level } => {
+                write!(f, "no room for another {:?} weight", level)
+            }
+            TailoringError::InvalidSetting { key, value } => {
+                write!(f, "invalid value {:?} for [{}]", value, key)
+            }
+        }
+    }
+}
+
+impl error::Error for TailoringError {}
+
+/// Errors that can occur while generating a sort key, see
+/// [`CollationElementTable::try_generate_sort_key`](crate::CollationElementTable::try_generate_sort_key).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollationError {
+    /// A character is not in the table and would get the implicit weights
+    /// of an unassigned code point.
+    MissingChar(char),
+}
+
+impl fmt::Display for CollationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollationError::MissingChar(c) => {
+                write!(f, "no collation elements for {:?} (U+{:04X})", c, *c as u32)
+            }
+        }
+    }
+}
+
+impl error::Error for CollationError {}
+
+/// A tailoring relation or setting that has no effect at the strength of the
+/// collator, see [`Collator::with_tailoring_warnings`](crate::Collator::with_tailoring_warnings).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildWarning {
+    /// The sequence of the relation, the list of a `<*` relation, or the
+    /// setting, like `[caseFirst upper]`.
+    pub sequence: String,
+    /// The strength that the relation or setting needs to be visible.
+    pub strength: Strength,
+}
+
+impl fmt::Display for BuildWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} needs strength {:?}, but the collator has a lower one",
+            self.sequence, self.strength
+        )
+    }
+}
+
+/// A line of a collation element table that was skipped because it could not
+/// be parsed, see [`CollationElementTable::from_lenient`](crate::CollationElementTable::from_lenient).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    /// The number of the line, starting at 1.
+    pub line: usize,
+    /// The contents of the line.
+    pub content: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "skipped invalid line {}: {:?}", self.line, self.content)
+    }
+}
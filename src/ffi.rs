@@ -0,0 +1,190 @@
+//! A C interface to the root collator.
+//!
+//! Strings are passed as a pointer to UTF-8 bytes with a length, so they
+//! don't have to be null-terminated. A null pointer is only allowed for an
+//! empty string. Functions that can fail return one of the negative error
+//! codes below.
+
+use crate::Collator;
+use std::{cmp::Ordering, ptr, slice, str};
+
+/// A pointer that has to be set is null.
+pub const COLLATE_NULL_POINTER: i32 = -2;
+/// A string is not valid UTF-8.
+pub const COLLATE_INVALID_UTF8: i32 = -3;
+/// A buffer is too small for the sort key.
+pub const COLLATE_BUFFER_TOO_SMALL: i32 = -4;
+
+// The string of `len` bytes at `ptr`, or the error code if it is invalid
+unsafe fn to_str<'a>(ptr: *const u8, len: usize) -> Result<&'a str, i32> {
+    if len == 0 {
+        return Ok("");
+    }
+    if ptr.is_null() {
+        return Err(COLLATE_NULL_POINTER);
+    }
+    str::from_utf8(slice::from_raw_parts(ptr, len)).map_err(|_| COLLATE_INVALID_UTF8)
+}
+
+/// Creates a collator for the root collation, which has to be freed with
+/// [`collate_collator_free`].
+#[no_mangle]
+pub extern "C" fn collate_collator_new() -> *mut Collator {
+    Box::into_raw(Box::new(Collator::root()))
+}
+
+/// Frees a collator from [`collate_collator_new`]. Null is ignored.
+///
+/// # Safety
+///
+/// `collator` has to be null or a collator that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn collate_collator_free(collator: *mut Collator) {
+    if !collator.is_null() {
+        drop(Box::from_raw(collator));
+    }
+}
+
+/// Compares the strings `a` and `b`, returning `-1`, `0` or `1` if `a` sorts
+/// before, the same as or after `b`, or an error code.
+///
+/// # Safety
+///
+/// `collator` has to be null or a live collator, and `a` and `b` have to be
+/// null or point to at least `a_len` and `b_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn collate_compare(
+    collator: *const Collator,
+    a: *const u8,
+    a_len: usize,
+    b: *const u8,
+    b_len: usize,
+) -> i32 {
+    let collator = match collator.as_ref() {
+        Some(collator) => collator,
+        None => return COLLATE_NULL_POINTER,
+    };
+    match (to_str(a, a_len), to_str(b, b_len)) {
+        (Ok(a), Ok(b)) => match collator.compare(a, b) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        },
+        (Err(code), _) | (_, Err(code)) => code,
+    }
+}
+
+/// Writes the sort key of `s` as bytes, like `SortKey::to_bytes`, to the
+/// buffer `buf` of `buf_len` bytes, which can then be compared with `memcmp`.
+/// The length of the key is written to `key_len`, also if the buffer is too
+/// small, so that a buffer of the right size can be allocated for a second
+/// call. The buffer can be null if `buf_len` is 0.
+///
+/// Returns 0 if the key was written, or an error code.
+///
+/// # Safety
+///
+/// `collator` has to be null or a live collator, `s` has to be null or point
+/// to at least `s_len` readable bytes, `buf` has to be null or point to at
+/// least `buf_len` writable bytes and `key_len` has to be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn collate_sort_key(
+    collator: *const Collator,
+    s: *const u8,
+    s_len: usize,
+    buf: *mut u8,
+    buf_len: usize,
+    key_len: *mut usize,
+) -> i32 {
+    let collator = match collator.as_ref() {
+        Some(collator) => collator,
+        None => return COLLATE_NULL_POINTER,
+    };
+    if key_len.is_null() {
+        return COLLATE_NULL_POINTER;
+    }
+    let s = match to_str(s, s_len) {
+        Ok(s) => s,
+        Err(code) => return code,
+    };
+
+    let bytes = collator.sort_key(s).to_bytes();
+    *key_len = bytes.len();
+    if bytes.len() > buf_len {
+        return COLLATE_BUFFER_TOO_SMALL;
+    }
+    if buf.is_null() {
+        return COLLATE_NULL_POINTER;
+    }
+    ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn c_interface() {
+        let collator = collate_collator_new();
+        let compare = |a: &[u8], b: &[u8]| unsafe {
+            collate_compare(collator, a.as_ptr(), a.len(), b.as_ptr(), b.len())
+        };
+        assert_eq!(compare(b"a", b"B"), -1);
+        assert_eq!(compare(b"b", b"A"), 1);
+        assert_eq!(compare("\u{e9}".as_bytes(), b"e\xcc\x81"), 0);
+        assert_eq!(compare(b"", b"a"), -1);
+        assert_eq!(compare(b"a\xff", b"a"), COLLATE_INVALID_UTF8);
+        unsafe {
+            assert_eq!(collate_compare(collator, ptr::null(), 0, ptr::null(), 0), 0);
+            assert_eq!(
+                collate_compare(collator, ptr::null(), 1, b"a".as_ptr(), 1),
+                COLLATE_NULL_POINTER
+            );
+            assert_eq!(
+                collate_compare(ptr::null(), b"a".as_ptr(), 1, b"a".as_ptr(), 1),
+                COLLATE_NULL_POINTER
+            );
+        }
+
+        // The length of the key can be asked for first
+        let s = b"Cargo.toml";
+        let mut len = 0;
+        let code = unsafe {
+            collate_sort_key(collator, s.as_ptr(), s.len(), ptr::null_mut(), 0, &mut len)
+        };
+        assert_eq!(code, COLLATE_BUFFER_TOO_SMALL);
+        let expected = Collator::root().sort_key("Cargo.toml").to_bytes();
+        assert_eq!(len, expected.len());
+
+        let mut buf = vec![0; len];
+        let code = unsafe {
+            collate_sort_key(
+                collator,
+                s.as_ptr(),
+                s.len(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut len,
+            )
+        };
+        assert_eq!(code, 0);
+        assert_eq!(buf, expected);
+        let code = unsafe {
+            collate_sort_key(
+                collator,
+                b"\xc3".as_ptr(),
+                1,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut len,
+            )
+        };
+        assert_eq!(code, COLLATE_INVALID_UTF8);
+
+        unsafe {
+            collate_collator_free(collator);
+            collate_collator_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{CollationElement, CollationElementTable, ImplicitRange};
+
+// The code points with the Unified_Ideograph property and the Unicode version
+// that assigned them. The core ideographs are those in the CJK Unified
+// Ideographs and CJK Compatibility Ideographs blocks.
+const CORE_IDEOGRAPHS: &[(u32, u32, (u8, u8))] = &[
+    (0x4E00, 0x9FA5, (1, 1)),
+    (0x9FA6, 0x9FBB, (4, 1)),
+    (0x9FBC, 0x9FC3, (5, 1)),
+    (0x9FC4, 0x9FCB, (5, 2)),
+    (0x9FCC, 0x9FCC, (6, 1)),
+    (0x9FCD, 0x9FD5, (8, 0)),
+    (0x9FD6, 0x9FEA, (10, 0)),
+    (0x9FEB, 0x9FEF, (11, 0)),
+    (0x9FF0, 0x9FFC, (13, 0)),
+    (0x9FFD, 0x9FFF, (14, 0)),
+    (0xFA0E, 0xFA0F, (1, 1)),
+    (0xFA11, 0xFA11, (1, 1)),
+    (0xFA13, 0xFA14, (1, 1)),
+    (0xFA1F, 0xFA1F, (1, 1)),
+    (0xFA21, 0xFA21, (1, 1)),
+    (0xFA23, 0xFA24, (1, 1)),
+    (0xFA27, 0xFA29, (1, 1)),
+];
+
+const EXTENSION_IDEOGRAPHS: &[(u32, u32, (u8, u8))] = &[
+    (0x3400, 0x4DB5, (3, 0)),
+    (0x4DB6, 0x4DBF, (13, 0)),
+    (0x20000, 0x2A6D6, (3, 1)),
+    (0x2A6D7, 0x2A6DD, (13, 0)),
+    (0x2A6DE, 0x2A6DF, (14, 0)),
+    (0x2A700, 0x2B734, (5, 2)),
+    (0x2B735, 0x2B738, (14, 0)),
+    (0x2B739, 0x2B739, (15, 0)),
+    (0x2B740, 0x2B81D, (6, 0)),
+    (0x2B820, 0x2CEA1, (8, 0)),
+    (0x2CEB0, 0x2EBE0, (10, 0)),
+    (0x2EBF0, 0x2EE5D, (15, 1)),
+    (0x30000, 0x3134A, (13, 0)),
+    (0x31350, 0x323AF, (15, 0)),
+];
+
+// The primary weights that UCA reserves for U+FFFD REPLACEMENT CHARACTER and
+// for U+FFFF, which sorts after everything else
+const REPLACEMENT_PRIMARY: u16 = 0xFFFD;
+const LAST_RESORT_PRIMARY: u16 = 0xFFFF;
+
+impl CollationElementTable {
+    // The implicit weights of a character that is not in the table, as
+    // described in section 10.1 of UTS #10. Ideographs that are not assigned
+    // in the version of the table are weighted like unassigned code points,
+    // and so are private use characters and noncharacters, which puts them
+    // after all assigned characters in code point order. Tables without a
+    // version get the ideographs of all known versions.
+    // U+FFFD and U+FFFF get their special weights if the table doesn't list
+    // them, so they come after all implicit weights.
+    pub(crate) fn implicit_elements(&self, c: char) -> Vec<CollationElement> {
+        let special = match c {
+            '\u{fffd}' => Some(REPLACEMENT_PRIMARY),
+            '\u{ffff}' => Some(LAST_RESORT_PRIMARY),
+            _ => None,
+        };
+        if let Some(primary) = special {
+            return vec![CollationElement {
+                variable: false,
+                tertiary_class: 0x02,
+                primary,
+                secondary: 0x0020,
+                tertiary: 0x0002,
+            }];
+        }
+
+        let cp = c as u32;
+        let (base, offset) = self
+            .assigned_implicit(cp)
+            .unwrap_or((0xFBC0 + (cp >> 15) as u16, cp & 0x7FFF));
+
+        vec![
+            CollationElement {
+                variable: false,
+                tertiary_class: 0x02,
+                primary: base,
+                secondary: 0x0020,
+                tertiary: 0x0002,
+            },
+            CollationElement {
+                variable: false,
+                tertiary_class: 0,
+                primary: offset as u16 | 0x8000,
+                secondary: 0,
+                tertiary: 0,
+            },
+        ]
+    }
+
+    // Whether a character that is not in the table still gets meaningful
+    // implicit weights, instead of the ones for unassigned code points
+    pub(crate) fn has_implicit_weights(&self, c: char) -> bool {
+        matches!(c, '\u{fffd}' | '\u{ffff}') || self.assigned_implicit(c as u32).is_some()
+    }
+
+    // The base and offset of the implicit weights of an ideograph or of a
+    // code point in an `@implicitweights` range
+    fn assigned_implicit(&self, cp: u32) -> Option<(u16, u32)> {
+        let assigned = |ranges: &[(u32, u32, (u8, u8))]| {
+            ranges.iter().any(|&(start, end, version)| {
+                start <= cp && cp <= end && self.header.unicode_version.is_none_or(|v| version <= v)
+            })
+        };
+
+        if let Some(ImplicitRange { range, base }) = self
+            .header
+            .implicit_weights
+            .iter()
+            .find(|r| r.range.contains(&cp))
+        {
+            Some((*base, cp - range.start()))
+        } else if assigned(CORE_IDEOGRAPHS) {
+            Some((0xFB40 + (cp >> 15) as u16, cp & 0x7FFF))
+        } else if assigned(EXTENSION_IDEOGRAPHS) {
+            Some((0xFB80 + (cp >> 15) as u16, cp & 0x7FFF))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn implicit_weights_ranges() {
+        let table = CollationElementTable::from(
+            "@version 13.0.0\n\
+             @implicitweights 17000..18AFF; FB00 # Tangut\n\
+             0061 ; [.0001.0020.0002]\n",
+        )
+        .unwrap();
+
+        let a = table.generate_sort_key("\u{17000}");
+        let b = table.generate_sort_key("\u{17001}");
+        assert_eq!(a.primary, [0xFB00, 0x8000]);
+        assert_eq!(b.primary, [0xFB00, 0x8001]);
+        assert!(table.generate_sort_key("a") < a && a < b);
+
+        // Implicit weights don't cut off the rest of the string
+        assert_eq!(
+            table.generate_sort_key("\u{17000}a").primary,
+            [0xFB00, 0x8000, 1]
+        );
+    }
+
+    #[test]
+    fn last_resort() {
+        let table = CollationElementTable::from("0061 ; [.0001.0020.0002]\n").unwrap();
+
+        // Letters, ideographs, unassigned code points, U+FFFD and U+FFFF
+        let v = [
+            "a",
+            "\u{4e00}",
+            "\u{378}",
+            "\u{10fffd}",
+            "\u{fffd}",
+            "\u{ffff}",
+        ];
+        for pair in v.windows(2) {
+            assert!(
+                table.generate_sort_key(pair[0]) < table.generate_sort_key(pair[1]),
+                "{:?}",
+                pair
+            );
+        }
+        assert_eq!(table.generate_sort_key("\u{fffd}").primary, [0xFFFD]);
+        assert_eq!(table.generate_sort_key("\u{ffff}").primary, [0xFFFF]);
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn last_resort_in_ducet() {
+        let table = CollationElementTable::default();
+        let mut v = [
+            "\u{ffff}", "\u{fffd}", "\u{378}", "z", "\u{4e00}", "\u{5d0}",
+        ];
+        v.sort_by_key(|s| table.generate_sort_key(s));
+        assert_eq!(
+            v,
+            ["z", "\u{5d0}", "\u{4e00}", "\u{378}", "\u{fffd}", "\u{ffff}"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn private_use_and_noncharacters() {
+        let table = CollationElementTable::default();
+        // In code point order, mixed with unassigned code points
+        let v = [
+            "z",
+            "\u{4e00}",
+            "\u{378}",
+            "\u{e000}",
+            "\u{e001}",
+            "\u{f8ff}",
+            "\u{fdd0}",
+            "\u{fffe}",
+            "\u{1fffe}",
+            "\u{f0000}",
+            "\u{10fffd}",
+            "\u{10ffff}",
+            "\u{fffd}",
+        ];
+        for pair in v.windows(2) {
+            assert!(
+                table.generate_sort_key(pair[0]) < table.generate_sort_key(pair[1]),
+                "{:?}",
+                pair
+            );
+        }
+        assert_eq!(
+            table.generate_sort_key("\u{f0000}").primary,
+            [0xFBDE, 0x8000]
+        );
+    }
+
+    #[test]
+    fn ideographs_of_version() {
+        let table = |version| {
+            CollationElementTable::from(&format!(
+                "@version {}\n0061 ; [.0001.0020.0002]\n",
+                version
+            ))
+            .unwrap()
+        };
+
+        // U+9FF0 was added in Unicode 13.0
+        let old = table("12.1.0");
+        assert_eq!(old.generate_sort_key("\u{9ff0}").primary, [0xFBC1, 0x9FF0]);
+        assert_eq!(old.generate_sort_key("\u{4e00}").primary, [0xFB40, 0xCE00]);
+        assert_eq!(old.generate_sort_key("\u{3400}").primary, [0xFB80, 0xB400]);
+
+        let new = table("13.0.0");
+        assert_eq!(new.generate_sort_key("\u{9ff0}").primary, [0xFB41, 0x9FF0]);
+
+        // Unassigned code points and private use characters
+        assert_eq!(new.generate_sort_key("\u{e000}").primary, [0xFBC1, 0xE000]);
+    }
+}
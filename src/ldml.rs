@@ -2,7 +2,7 @@ use strong_xml::XmlRead;
 
 #[derive(Debug, XmlRead, PartialEq)]
 #[xml(tag = "ldml")]
-pub struct LDML {
+pub struct Ldml {
     #[xml(child = "identity")]
     pub identity: IdentityTag,
     #[xml(child = "collations")]
@@ -44,6 +44,8 @@ pub struct Territory {
 #[derive(Debug, XmlRead, PartialEq)]
 #[xml(tag = "collations")]
 pub struct Collations {
+    #[xml(flatten_text = "defaultCollation")]
+    pub default_collation: Option<String>,
     #[xml(child = "collation")]
     pub collation: Vec<Collation>,
 }
@@ -64,7 +66,7 @@ mod tests {
     #[test]
     fn test_tailoring() {
         assert_eq!(
-            LDML::from_str(
+            Ldml::from_str(
                 "<ldml>
                     <identity>
                         <version number=\"$Revision$\"/>
@@ -78,7 +80,7 @@ mod tests {
                 </ldml>",
             )
             .unwrap(),
-            LDML {
+            Ldml {
                 identity: IdentityTag {
                     version: Version {
                         number: "$Revision$".into()
@@ -89,6 +91,7 @@ mod tests {
                     territory: None,
                 },
                 collations: Collations {
+                    default_collation: None,
                     collation: vec![Collation {
                         r#type: "standard".into(),
                         rules: vec!["&N<<<ŉ".into()]
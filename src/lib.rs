@@ -23,108 +23,872 @@
 ///       * Append the CE_L values from that list to the sort key
 /// * Compare the keys, easy peasy
 mod parse_cet;
+#[cfg(feature = "bundled-ducet")]
 mod ldml;
+#[cfg(feature = "bundled-ducet")]
 mod locale;
 pub mod collation_rules;
-use std::{cmp::Ordering, collections::BTreeMap, iter::Peekable, ops::Deref, str::Chars};
+pub mod prelude;
+mod ascii;
+mod cache;
+mod collator;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod implicit;
+mod reorder;
+mod tailoring;
+mod well_formed;
+pub use ascii::AsciiCollator;
+pub use collator::{
+    CaseFirst, CollatedString, Collator, CollatorView, Decomposition, IndexScript, MaxVariable,
+    Strength, TertiaryFeature, Tristate, VariableWeighting,
+};
+pub use error::{BuildWarning, CollationError, Error, ParseWarning, TailoringError};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    iter::Peekable,
+    ops::{Bound, Deref, DerefMut, Index, RangeInclusive},
+    str::Chars,
+};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+pub use well_formed::WellFormednessViolation;
 
 use unic_normal::{Decompositions, StrNormalForm};
+use unic_ucd_category::GeneralCategory;
+use unic_ucd_normal::CanonicalCombiningClass;
+
+// The length of the longest run of non-starters in the Stream-Safe Text Format
+// of UAX #15, which is as far as a mark is looked for to extend a contraction
+const MAX_NON_STARTERS: usize = 30;
 
 // Default Unicode Collation Element Table (adjusted for CLDR)
-static DUCET: &'static str = include_str!("../cldr/common/uca/allkeys_CLDR.txt");
+#[cfg(feature = "bundled-ducet")]
+static DUCET: &str = include_str!("../cldr/common/uca/allkeys_CLDR.txt");
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+/// A collation element. Elements are ordered by their primary, secondary and
+/// tertiary weights. The variable flag only breaks ties, because it doesn't
+/// change where an element sorts, only how it is weighted.
+#[derive(PartialEq, Eq, Clone, Debug)]
 pub struct CollationElement {
     variable: bool,
+    // The tertiary weight of the element before tailoring, which tells case,
+    // width, kana and compatibility variants apart (table 14 of UTS #10).
+    // Tailoring can move the tertiary weight, but not this.
+    tertiary_class: u8,
     primary: u16,
     secondary: u16,
     tertiary: u16,
 }
 
+impl PartialOrd for CollationElement {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CollationElement {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (
+            self.primary,
+            self.secondary,
+            self.tertiary,
+            self.variable,
+            self.tertiary_class,
+        )
+            .cmp(&(
+                other.primary,
+                other.secondary,
+                other.tertiary,
+                other.variable,
+                other.tertiary_class,
+            ))
+    }
+}
+
+// The collation elements of an entry in a table. Most entries have a single
+// element, which is stored inline to save memory.
+#[derive(Clone, PartialEq, Eq)]
+struct Elements(ElementsRepr);
+
+#[derive(Clone, PartialEq, Eq)]
+enum ElementsRepr {
+    One(CollationElement),
+    Many(Box<[CollationElement]>),
+}
+
+impl From<Vec<CollationElement>> for Elements {
+    fn from(mut elements: Vec<CollationElement>) -> Self {
+        Self(match elements.len() {
+            1 => ElementsRepr::One(elements.pop().unwrap()),
+            _ => ElementsRepr::Many(elements.into_boxed_slice()),
+        })
+    }
+}
+
+impl Deref for Elements {
+    type Target = [CollationElement];
+
+    fn deref(&self) -> &Self::Target {
+        match &self.0 {
+            ElementsRepr::One(elem) => std::slice::from_ref(elem),
+            ElementsRepr::Many(elements) => elements,
+        }
+    }
+}
+
+impl DerefMut for Elements {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match &mut self.0 {
+            ElementsRepr::One(elem) => std::slice::from_mut(elem),
+            ElementsRepr::Many(elements) => elements,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Elements {
+    type Item = &'a CollationElement;
+    type IntoIter = std::slice::Iter<'a, CollationElement>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for Elements {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// A table mapping (sequences of) characters to their collation elements.
+///
+/// Generating sort keys doesn't modify the table, so it is `Send + Sync` and
+/// can be parsed once and shared between threads, for example with an `Arc`:
+///
+/// ```
+/// use collate::CollationElementTable;
+/// use std::{sync::Arc, thread};
+///
+/// let table = Arc::new(CollationElementTable::default());
+/// let handles: Vec<_> = (0..2)
+///     .map(|_| {
+///         let table = Arc::clone(&table);
+///         thread::spawn(move || {
+///             let mut v = ["b", "A", "a"];
+///             v.sort_by_key(|s| table.generate_sort_key(s));
+///             v
+///         })
+///     })
+///     .collect();
+///
+/// for handle in handles {
+///     assert_eq!(handle.join().unwrap(), ["a", "A", "b"]);
+/// }
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct CollationElementTable {
-    data: BTreeMap<String, Vec<CollationElement>>,
+    // Boxed keys and inline elements keep the nodes of the map small
+    data: BTreeMap<Box<str>, Elements>,
+    // Length in chars of the longest key, which bounds the contraction lookahead
+    max_contraction_len: usize,
+    // Prefixes of keys that are not keys themselves, like `abc` if there are
+    // only `ab` and `abcd`, which the lookahead has to continue past
+    contraction_gaps: BTreeSet<Box<str>>,
+    header: TableHeader,
+    // Entries that only apply after a prefix, by key and then by prefix, and
+    // the length in chars of the longest prefix
+    prefixed: BTreeMap<String, Vec<(String, Vec<CollationElement>)>>,
+    max_prefix_len: usize,
+    // Whether a key starts with two ASCII characters, which rules out looking
+    // up ASCII characters one by one
+    ascii_contractions: bool,
+    // The weights that tailoring moved the tertiary weights of the table to,
+    // by the weight before, or empty if it moved none. Removing a tertiary
+    // difference maps the weight without it through this.
+    moved_tertiaries: Vec<u16>,
+}
+
+/// The directives at the start of a [`CollationElementTable`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableHeader {
+    version: Option<String>,
+    // The major and minor version, which determine the assigned characters
+    unicode_version: Option<(u8, u8)>,
+    implicit_weights: Vec<ImplicitRange>,
+}
+
+impl TableHeader {
+    /// The Unicode version of the table from the `@version` line, like
+    /// `13.0.0`.
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// The ranges of code points from the `@implicitweights` lines.
+    pub fn implicit_weights(&self) -> &[ImplicitRange] {
+        &self.implicit_weights
+    }
+}
+
+/// A range of code points whose implicit weights are computed from their own
+/// base primary weight, like the Tangut characters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImplicitRange {
+    pub range: RangeInclusive<u32>,
+    pub base: u16,
 }
 
 impl CollationElementTable {
     pub fn from(i: &str) -> Result<Self, nom::Err<nom::error::Error<&str>>> {
-        let mut data = BTreeMap::new();
-        parse_cet::table(i, &mut data)?;
-        Ok(Self { data })
+        Ok(Self::from_with_warnings(i)?.0)
     }
 
-    pub fn generate_sort_key(&self, s: &str) -> SortKey {
-        let mut key = SortKey::new();
-        for elem in CollationElements::from(self, s).flatten() {
-            if elem.primary != 0 {
-                key.primary.push(elem.primary);
+    /// Parses a table like [`CollationElementTable::from`], and also returns
+    /// the rows that were skipped because they contain a code point that
+    /// isn't a `char`, like a surrogate, as warnings.
+    pub fn from_with_warnings(
+        i: &str,
+    ) -> Result<(Self, Vec<ParseWarning>), nom::Err<nom::error::Error<&str>>> {
+        let mut table = Self::empty();
+        let (_, warnings) = parse_cet::table(i, &mut table, |_, _| true)?;
+        table.index_keys();
+        Ok((table, warnings))
+    }
+
+    /// Parses a table like [`CollationElementTable::from`], but only keeps the
+    /// entries of keys that consist of characters for which `keep` is true,
+    /// which saves memory when only some scripts are sorted. Other characters
+    /// get implicit weights, which sort them after the kept ones.
+    ///
+    /// Entries without primary weights, like those of combining marks, are
+    /// always kept. Keys are in NFD, so accented letters are composed of the
+    /// base letter and these marks.
+    pub fn from_filtered(
+        i: &str,
+        keep: impl Fn(char) -> bool,
+    ) -> Result<Self, nom::Err<nom::error::Error<&str>>> {
+        let mut table = Self::empty();
+        parse_cet::table(i, &mut table, |key, elements| {
+            key.chars().all(&keep) || elements.iter().all(|e| e.primary == 0)
+        })?;
+        table.index_keys();
+        Ok(table)
+    }
+
+    /// Parses a table like [`CollationElementTable::from`], but skips the
+    /// lines that can't be parsed instead of failing. The skipped lines are
+    /// returned as warnings, together with the rows that contain a code point
+    /// that isn't a `char`.
+    pub fn from_lenient(i: &str) -> (Self, Vec<ParseWarning>) {
+        let mut table = Self::empty();
+        let warnings = parse_cet::table_lenient(i, &mut table);
+        table.index_keys();
+        (table, warnings)
+    }
+
+    /// The `@version` and `@implicitweights` directives of the table.
+    pub fn header(&self) -> &TableHeader {
+        &self.header
+    }
+
+    /// The collation elements of `key`, a character or a contraction in
+    /// NFD, if it has an entry in the table. Entries that only apply after a
+    /// prefix are not included.
+    pub fn get(&self, key: &str) -> Option<&[CollationElement]> {
+        self.data.get(key).map(|elements| &elements[..])
+    }
+
+    /// Whether `key` has an entry in the table, see [`get`](Self::get).
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.data.contains_key(key)
+    }
+
+    /// The entries of the table with their collation elements, ordered by
+    /// key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[CollationElement])> + '_ {
+        self.data
+            .iter()
+            .map(|(key, elements)| (&**key, &elements[..]))
+    }
+
+    /// The number of entries in the table.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Whether the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn empty() -> Self {
+        Self {
+            data: BTreeMap::new(),
+            max_contraction_len: 0,
+            contraction_gaps: BTreeSet::new(),
+            header: TableHeader::default(),
+            prefixed: BTreeMap::new(),
+            max_prefix_len: 0,
+            ascii_contractions: false,
+            moved_tertiaries: Vec::new(),
+        }
+    }
+
+    // Updates the properties of the keys that lookups depend on
+    fn index_keys(&mut self) {
+        self.max_contraction_len = self
+            .data
+            .keys()
+            .map(|k| k.chars().count())
+            .max()
+            .unwrap_or(0);
+        self.ascii_contractions = self.data.keys().any(|k| starts_with_ascii_pair(k));
+        let data = &self.data;
+        self.contraction_gaps = data
+            .keys()
+            .flat_map(|k| k.char_indices().skip(1).map(move |(i, _)| &k[..i]))
+            .filter(|prefix| !data.contains_key(*prefix))
+            .map(Box::from)
+            .collect();
+    }
+
+    // Updates the gaps for a key that was just added
+    fn index_gaps(&mut self, key: &str) {
+        self.contraction_gaps.remove(key);
+        for (i, _) in key.char_indices().skip(1) {
+            if !self.data.contains_key(&key[..i]) {
+                self.contraction_gaps.insert(key[..i].into());
             }
-            if elem.secondary != 0 {
-                key.secondary.push(elem.secondary);
+        }
+    }
+
+    // The weight that elements with the tertiary weight `tertiary` before
+    // tailoring have now
+    fn moved_tertiary(&self, tertiary: u16) -> u16 {
+        match self.moved_tertiaries.get(usize::from(tertiary)) {
+            Some(&moved) => moved,
+            None => tertiary,
+        }
+    }
+
+    // Whether a key that is longer than `s` starts with it
+    fn has_longer_key(&self, s: &str) -> bool {
+        let after = (Bound::Excluded(s), Bound::Unbounded);
+        match self.data.range::<str, _>(after).next() {
+            Some((key, _)) => key.starts_with(s),
+            None => false,
+        }
+    }
+
+    /// Reads and parses the collation element table in the file at `path`.
+    #[cfg(feature = "std")]
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let s = fs::read_to_string(path)?;
+        Self::from(&s).map_err(|e| Error::Table {
+            line: error_line(&s, e),
+        })
+    }
+
+    /// Adds all entries of `other` to the table. Entries for keys that are
+    /// already in the table are replaced by the ones from `other`.
+    pub fn merge(&mut self, other: &CollationElementTable) {
+        self.data
+            .extend(other.data.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self.max_contraction_len = self.max_contraction_len.max(other.max_contraction_len);
+        self.ascii_contractions |= other.ascii_contractions;
+        for key in other.data.keys() {
+            self.index_gaps(key);
+        }
+        if self.header.version.is_none() {
+            self.header.version = other.header.version.clone();
+            self.header.unicode_version = other.header.unicode_version;
+        }
+        self.header
+            .implicit_weights
+            .extend(other.header.implicit_weights.iter().cloned());
+        for (key, entries) in &other.prefixed {
+            for (prefix, elements) in entries {
+                self.insert_prefixed(key.clone(), prefix.clone(), elements.clone());
             }
-            if elem.tertiary != 0 {
-                key.tertiary.push(elem.tertiary)
+        }
+    }
+
+    /// Parses the rows of another table in the same format as
+    /// [`CollationElementTable::from`] and merges them into this one, see
+    /// [`CollationElementTable::merge`]. The table is left unchanged if the
+    /// rows can't be parsed.
+    pub fn extend_from_str<'a>(
+        &mut self,
+        i: &'a str,
+    ) -> Result<(), nom::Err<nom::error::Error<&'a str>>> {
+        self.merge(&Self::from(i)?);
+        Ok(())
+    }
+
+    fn insert_prefixed(&mut self, key: String, prefix: String, elements: Vec<CollationElement>) {
+        self.max_prefix_len = self.max_prefix_len.max(prefix.chars().count());
+        let entries = self.prefixed.entry(key).or_default();
+        entries.retain(|(p, _)| *p != prefix);
+        entries.push((prefix, elements));
+    }
+
+    // The elements of `key` for the longest prefix that `preceding` ends with
+    fn prefixed_elements(&self, preceding: &str, key: &str) -> Option<&[CollationElement]> {
+        self.prefixed
+            .get(key)?
+            .iter()
+            .filter(|(prefix, _)| preceding.ends_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, elements)| elements.as_slice())
+    }
+
+    // The length of the leading run of ASCII characters in `s` that can be
+    // looked up one by one, without normalization or contractions
+    fn ascii_prefix_len(&self, s: &str) -> usize {
+        if self.ascii_contractions || self.max_prefix_len > 0 {
+            return 0;
+        }
+        let len = ascii_len(s.as_bytes());
+        // The last one could start a contraction with the character after it
+        if len < s.len() {
+            len.saturating_sub(1)
+        } else {
+            len
+        }
+    }
+
+    /// Writes the table in the format of `allkeys.txt`, which
+    /// [`CollationElementTable::from`] parses back into an equal table. This
+    /// is useful for comparing a tailored table to the original. Entries with
+    /// a prefix can't be expressed in the format and are left out.
+    pub fn to_allkeys_string(&self) -> String {
+        let mut s = String::new();
+        if let Some(version) = &self.header.version {
+            s.push_str(&format!("@version {}\n", version));
+        }
+        for ImplicitRange { range, base } in &self.header.implicit_weights {
+            s.push_str(&format!(
+                "@implicitweights {:04X}..{:04X}; {:04X}\n",
+                range.start(),
+                range.end(),
+                base
+            ));
+        }
+        for (key, elements) in &self.data {
+            let code_points: Vec<_> = key.chars().map(|c| format!("{:04X}", c as u32)).collect();
+            s.push_str(&code_points.join(" "));
+            s.push_str(" ; ");
+            for elem in elements {
+                s.push_str(&format!(
+                    "[{}{:04X}.{:04X}.{:04X}]",
+                    if elem.variable { '*' } else { '.' },
+                    elem.primary,
+                    elem.secondary,
+                    elem.tertiary
+                ));
             }
+            s.push('\n');
+        }
+        s
+    }
+
+    /// Returns the collation elements of `c` in the table, or `None` if it is
+    /// not in the table and gets implicit weights instead. Contractions that
+    /// start with `c` are not considered.
+    pub fn weights_of(&self, c: char) -> Option<&[CollationElement]> {
+        self.data.get(&*c.encode_utf8(&mut [0; 4])).map(|e| &e[..])
+    }
+
+    /// Returns the entries with keys of more than one character, in the order
+    /// of their keys.
+    pub fn contractions(&self) -> impl Iterator<Item = (&str, &[CollationElement])> {
+        self.data
+            .iter()
+            .filter(|(k, _)| k.chars().nth(1).is_some())
+            .map(|(k, v)| (&**k, &**v))
+    }
+
+    /// Generates the sort key for `s`.
+    ///
+    /// The string is converted to NFD before looking up collation elements,
+    /// so input in any normalization form (e.g. NFC) is accepted and
+    /// canonically equivalent strings get the same sort key.
+    pub fn generate_sort_key(&self, s: &str) -> SortKey {
+        let mut key = SortKey::new();
+        for elem in CollationElements::from_str(self, s) {
+            key.push(&elem);
+        }
+        key
+    }
+
+    /// Generates the sort key for `s` like
+    /// [`generate_sort_key`](Self::generate_sort_key), but fails on the first
+    /// character that is not in the table, instead of giving it the implicit
+    /// weights of an unassigned code point. Ideographs, characters in the
+    /// `@implicitweights` ranges and U+FFFD still get their implicit weights,
+    /// because those are part of the table's order.
+    pub fn try_generate_sort_key(&self, s: &str) -> Result<SortKey, CollationError> {
+        let missing = s.nfd().find(|&c| {
+            self.get(&*c.encode_utf8(&mut [0; 4])).is_none() && !self.has_implicit_weights(c)
+        });
+        match missing {
+            Some(c) => Err(CollationError::MissingChar(c)),
+            None => Ok(self.generate_sort_key(s)),
+        }
+    }
+
+    /// Returns the canonical decomposition (NFD) of `s`, which is the form
+    /// that collation elements are looked up in. Passing the result to
+    /// [`generate_sort_key`](Self::generate_sort_key) gives the same key as
+    /// passing `s`, so normalized input can be cached.
+    pub fn normalize(&self, s: &str) -> String {
+        s.nfd().collect()
+    }
+
+    /// Generates the sort key for a sequence of characters, which is
+    /// normalized like the input of [`generate_sort_key`](Self::generate_sort_key).
+    pub fn generate_sort_key_from_chars<I: IntoIterator<Item = char>>(&self, chars: I) -> SortKey {
+        let mut key = SortKey::new();
+        for elem in CollationElements::from(self, chars.into_iter()) {
+            key.push(&elem);
         }
         key
     }
 }
 
-impl Deref for CollationElementTable {
-    type Target = BTreeMap<String, Vec<CollationElement>>;
+fn starts_with_ascii_pair(key: &str) -> bool {
+    key.len() >= 2 && key.as_bytes()[..2].is_ascii()
+}
 
-    fn deref(&self) -> &Self::Target {
-        &self.data
+// The length of the leading run of ASCII bytes. Checking whole chunks first
+// lets the compiler use wide (SIMD) loads for long runs.
+fn ascii_len(bytes: &[u8]) -> usize {
+    const CHUNK: usize = 32;
+    let mut len = 0;
+    for chunk in bytes.chunks_exact(CHUNK) {
+        if !chunk.is_ascii() {
+            break;
+        }
+        len += CHUNK;
     }
+    len + bytes[len..].iter().take_while(|b| b.is_ascii()).count()
 }
 
+// The line of `input` at which parsing failed
+#[cfg(feature = "std")]
+fn error_line(input: &str, e: nom::Err<nom::error::Error<&str>>) -> usize {
+    let rest = match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e.input,
+        nom::Err::Incomplete(_) => "",
+    };
+    input[..input.len() - rest.len()].matches('\n').count() + 1
+}
+
+impl Index<&str> for CollationElementTable {
+    type Output = [CollationElement];
+
+    /// The collation elements of `key`, see [`get`](Self::get).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` has no entry in the table.
+    fn index(&self, key: &str) -> &Self::Output {
+        self.get(key).expect("no entry for key")
+    }
+}
+
+#[cfg(feature = "bundled-ducet")]
 impl Default for CollationElementTable {
+    /// The root table (DUCET). Without the `bundled-ducet` feature, a table
+    /// has to be supplied with [`CollationElementTable::from`] instead.
     fn default() -> Self {
         // Assume that parsing DUCET will not throw an error
         Self::from(DUCET).unwrap()
     }
 }
 
-struct CollationElements<'a> {
-    normalized: Peekable<Decompositions<Chars<'a>>>,
+// The collation elements of a sequence of characters. Elements from the
+// table are borrowed, so only implicit weights are allocated.
+struct CollationElements<'a, I: Iterator<Item = char>> {
+    // ASCII characters that are looked up before the normalized ones
+    ascii: &'a str,
+    normalized: Peekable<Decompositions<I>>,
     table: &'a CollationElementTable,
+    // The last characters that were consumed, for matching prefixes
+    preceding: String,
+    // The key that is being looked up, kept to reuse its allocation
+    key: String,
+    // Characters that were read past the longest key, in reverse
+    pending: Vec<char>,
+    // Whether runs of decimal digits are weighted as numbers
+    numeric: bool,
+    // The elements of the last lookup and the number that was returned
+    current: Cow<'a, [CollationElement]>,
+    returned: usize,
 }
 
-impl<'a> CollationElements<'a> {
-    fn from(table: &'a CollationElementTable, s: &'a str) -> Self {
-        let normalized = s.nfd();
+impl<'a, I: Iterator<Item = char>> CollationElements<'a, I> {
+    fn from(table: &'a CollationElementTable, chars: I) -> Self {
+        let normalized = chars.nfd();
         Self {
             table,
+            ascii: "",
             normalized: normalized.peekable(),
+            preceding: String::new(),
+            key: String::new(),
+            pending: Vec::new(),
+            numeric: false,
+            current: Cow::Borrowed(&[]),
+            returned: 0,
         }
     }
-}
 
-impl<'a> Iterator for CollationElements<'a> {
-    type Item = Vec<CollationElement>;
+    // Looks up the elements of the longest key at the start of the rest of
+    // the characters
+    fn next_elements(&mut self) -> Option<Cow<'a, [CollationElement]>> {
+        let table = self.table;
+        if !self.ascii.is_empty() {
+            let (s, rest) = self.ascii.split_at(1);
+            self.ascii = rest;
+            return Some(match table.get(s) {
+                Some(elem) => Cow::Borrowed(elem),
+                None => Cow::Owned(table.implicit_elements(s.as_bytes()[0] as char)),
+            });
+        }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        // OPTIMIZE: Remove allocations and copying
-        let mut s = String::from(self.normalized.next()?);
-        let mut elem = self.table.get(&s)?;
-        while let Some(&c) = self.normalized.peek() {
+        let first = self.pending.pop().or_else(|| self.normalized.next())?;
+        if self.numeric && is_decimal_digit(first) {
+            return Some(Cow::Owned(self.number_elements(first)));
+        }
+        let (s, pending) = (&mut self.key, &mut self.pending);
+        s.clear();
+        s.push(first);
+        let mut elem = table.get(s.as_str());
+        // The length in bytes of the longest key that was found
+        let mut matched = s.len();
+        let mut len = 1;
+        // Probing stops at the first sequence that neither is a key nor
+        // continues into one, or when no key in the table is longer
+        while len < table.max_contraction_len
+            && (elem.is_some() || table.contraction_gaps.contains(s.as_str()))
+        {
+            let next = match pending.last() {
+                Some(c) => Some(c),
+                None => self.normalized.peek(),
+            };
+            let c = match next {
+                Some(&c) => c,
+                None => break,
+            };
             s.push(c);
-            if let Some(e) = self.table.get(&s) {
-                elem = e;
-                self.normalized.next();
-            } else {
+            if let Some(e) = table.get(s.as_str()) {
+                elem = Some(e);
+                matched = s.len();
+            } else if !table.contraction_gaps.contains(s.as_str()) {
                 s.pop();
                 break;
             }
+            if pending.pop().is_none() {
+                self.normalized.next();
+            }
+            len += 1;
+        }
+        // The characters of a gap after the longest key are read again
+        while s.len() > matched {
+            pending.extend(s.pop());
+        }
+
+        // A non-starter further on still extends the match if no mark in
+        // between has the same or a higher combining class, like the breve of
+        // `\u{418}\u{323}\u{306}` that makes `\u{418}\u{306}` a contraction.
+        // The marks that are skipped are read again after the match. Marks
+        // are only looked at while a longer key could still match, and not
+        // further than the stream-safe limit, so long runs of marks don't
+        // take quadratic time.
+        if elem.is_some() && table.max_contraction_len > 1 {
+            let mut marks = Vec::new();
+            let mut blocking = 0;
+            for _ in 0..MAX_NON_STARTERS {
+                if !table.has_longer_key(s) {
+                    break;
+                }
+                let next = match pending.last() {
+                    Some(&c) => c,
+                    None => match self.normalized.peek() {
+                        Some(&c) => c,
+                        None => break,
+                    },
+                };
+                let class = CanonicalCombiningClass::of(next);
+                if class.is_not_reordered() {
+                    break;
+                }
+                if pending.pop().is_none() {
+                    self.normalized.next();
+                }
+                if class.number() > blocking {
+                    s.push(next);
+                    if let Some(e) = table.get(s.as_str()) {
+                        elem = Some(e);
+                        continue;
+                    }
+                    s.pop();
+                }
+                blocking = blocking.max(class.number());
+                marks.push(next);
+            }
+            pending.extend(marks.into_iter().rev());
+        }
+
+        if table.max_prefix_len > 0 {
+            if let Some(e) = table.prefixed_elements(&self.preceding, s) {
+                elem = Some(e);
+            }
+            push_preceding(&mut self.preceding, s, table.max_prefix_len);
+        }
+
+        Some(match elem {
+            Some(elem) => Cow::Borrowed(elem),
+            None => Cow::Owned(table.implicit_elements(first)),
+        })
+    }
+
+    // The elements of the number that starts with the digit `first`, for
+    // numeric ordering, which are those of the ASCII digits. The count of
+    // significant digits comes first, so that longer numbers sort last, as
+    // `9` for every nine digits followed by the rest as a lower digit. Then
+    // come the digits themselves, without leading zeros.
+    fn number_elements(&mut self, first: char) -> Vec<CollationElement> {
+        let mut digits = String::new();
+        digits.push(first);
+        loop {
+            let next = match self.pending.last() {
+                Some(&c) => c,
+                None => match self.normalized.peek() {
+                    Some(&c) => c,
+                    None => break,
+                },
+            };
+            if !is_decimal_digit(next) {
+                break;
+            }
+            digits.push(next);
+            if self.pending.pop().is_none() {
+                self.normalized.next();
+            }
+        }
+        let table = self.table;
+        if table.max_prefix_len > 0 {
+            push_preceding(&mut self.preceding, &digits, table.max_prefix_len);
+        }
+
+        let mut values: Vec<_> = digits
+            .chars()
+            .map(digit_value)
+            .skip_while(|&d| d == 0)
+            .collect();
+        if values.is_empty() {
+            values.push(0);
+        }
+        let len = values.len() - 1;
+        let mut weights = vec![9; len / 9];
+        weights.push((len % 9) as u8);
+        weights.extend(values);
+        weights
+            .into_iter()
+            .flat_map(|d| {
+                let digit = char::from(b'0' + d);
+                match table.get(digit.encode_utf8(&mut [0; 4])) {
+                    Some(elements) => elements.to_vec(),
+                    None => table.implicit_elements(digit),
+                }
+            })
+            .collect()
+    }
+}
+
+// Adds the characters of `s` to the ones before the next key, of which only
+// the last `max_len` can be part of a prefix
+fn push_preceding(preceding: &mut String, s: &str, max_len: usize) {
+    preceding.push_str(s);
+    let excess = preceding.chars().count().saturating_sub(max_len);
+    if let Some((i, _)) = preceding.char_indices().nth(excess) {
+        preceding.drain(..i);
+    }
+}
+
+fn is_decimal_digit(c: char) -> bool {
+    GeneralCategory::of(c) == GeneralCategory::DecimalNumber
+}
+
+// The value of a decimal digit. Unicode encodes the digits of every script as
+// runs of ten from 0 to 9, so the value is the position in its run.
+fn digit_value(c: char) -> u8 {
+    let mut zero = c as u32;
+    while char::from_u32(zero - 1).is_some_and(is_decimal_digit) {
+        zero -= 1;
+    }
+    ((c as u32 - zero) % 10) as u8
+}
+
+impl<'a> CollationElements<'a, Chars<'a>> {
+    // ASCII is unchanged by normalization, so a leading run of it is looked
+    // up directly, which is a lot faster for mostly ASCII strings
+    fn from_str(table: &'a CollationElementTable, s: &'a str) -> Self {
+        let (ascii, rest) = s.split_at(table.ascii_prefix_len(s));
+        Self {
+            ascii,
+            ..Self::from(table, rest.chars())
+        }
+    }
+
+    // With numeric ordering, where the ASCII digits can't be looked up one by
+    // one
+    fn numeric(table: &'a CollationElementTable, s: &'a str) -> Self {
+        Self {
+            numeric: true,
+            ..Self::from(table, s.chars())
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = char>> Iterator for CollationElements<'a, I> {
+    type Item = CollationElement;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.returned == self.current.len() {
+            self.current = self.next_elements()?;
+            self.returned = 0;
         }
-        Some(elem.clone())
+        self.returned += 1;
+        Some(self.current[self.returned - 1].clone())
     }
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+/// The levels of a sort key, from most to least significant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Primary,
+    Secondary,
+    /// The case of letters, which is only in keys with a case level, see
+    /// [`Collator::set_case_level`].
+    Case,
+    Tertiary,
+    Quaternary,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct SortKey {
     primary: Vec<u16>,
     secondary: Vec<u16>,
+    case: Vec<u16>,
     tertiary: Vec<u16>,
+    quaternary: Vec<u16>,
 }
 
 impl SortKey {
@@ -132,19 +896,307 @@ impl SortKey {
         Self::default()
     }
 
+    // Appends the non-zero weights of the first three levels
+    fn push(&mut self, elem: &CollationElement) {
+        if elem.primary != 0 {
+            self.primary.push(elem.primary);
+        }
+        if elem.secondary != 0 {
+            self.secondary.push(elem.secondary);
+        }
+        if elem.tertiary != 0 {
+            self.tertiary.push(elem.tertiary)
+        }
+    }
+
+    // Keeps the first `len` weights, starting at the primary level. The
+    // levels after a cut off level are cleared, so this is a prefix of the
+    // key, which keeps the order of keys, except that some become equal.
+    fn truncate(&mut self, mut len: usize) {
+        let mut levels = [
+            &mut self.primary,
+            &mut self.secondary,
+            &mut self.case,
+            &mut self.tertiary,
+            &mut self.quaternary,
+        ];
+        for i in 0..levels.len() {
+            if levels[i].len() > len {
+                levels[i].truncate(len);
+                for level in &mut levels[i + 1..] {
+                    level.clear();
+                }
+                return;
+            }
+            len -= levels[i].len();
+        }
+    }
+
+    /// Compares two sort keys level by level, returning the ordering together
+    /// with the level at which the keys differ, or `None` if they are equal.
+    pub fn compare_detailed(&self, other: &SortKey) -> (Ordering, Option<Level>) {
+        let levels = [
+            (Level::Primary, &self.primary, &other.primary),
+            (Level::Secondary, &self.secondary, &other.secondary),
+            (Level::Case, &self.case, &other.case),
+            (Level::Tertiary, &self.tertiary, &other.tertiary),
+            (Level::Quaternary, &self.quaternary, &other.quaternary),
+        ];
+        for &(level, a, b) in &levels {
+            match a.cmp(b) {
+                Ordering::Equal => {}
+                ord => return (ord, Some(level)),
+            }
+        }
+        (Ordering::Equal, None)
+    }
+
+    /// Formats the weights of the key in hexadecimal, with the levels
+    /// separated by `|`, like `1FA2 1FE1 | 0020 0020 | 0002 0002 |`.
+    /// A case level is only shown if the key has one.
+    pub fn to_hex_string(&self) -> String {
+        let levels: Vec<String> = self
+            .levels()
+            .map(|level| {
+                level
+                    .iter()
+                    .map(|w| format!("{:04X}", w))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        levels.join(" | ").trim_end().to_owned()
+    }
+
+    /// Returns the key as bytes that compare like the key itself, so they can
+    /// be stored in place of it, for example in a database index.
+    ///
+    /// Like in ICU sort keys, the levels are separated by a `01` byte and the
+    /// bytes of the weights are at least `02`, so a separator always sorts
+    /// before a weight and the bytes never contain `00`. Weights are written
+    /// as two base-254 digits, or three digits starting with `FF` for the
+    /// highest weights. Runs of the common secondary weight are compressed to
+    /// a single weight, which makes the keys of text without accents much
+    /// shorter.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.byte_len());
+        self.encode(|code| match code {
+            Some(weight) => push_weight(&mut bytes, weight),
+            None => bytes.push(LEVEL_SEPARATOR),
+        });
+        bytes
+    }
+
+    /// Returns the length of [`SortKey::to_bytes`] without writing the bytes,
+    /// for example to size a buffer or a database column.
+    pub fn byte_len(&self) -> usize {
+        let mut len = 0;
+        self.encode(|code| {
+            len += match code {
+                Some(weight) if weight < TWO_DIGIT_WEIGHTS => 2,
+                Some(_) => 3,
+                None => 1,
+            }
+        });
+        len
+    }
+
+    /// Returns the byte bounds of the keys of all strings that start with the
+    /// string of this key at primary strength, for range queries on keys
+    /// from [`SortKey::to_bytes`]. The lower bound is inclusive and the upper
+    /// bound exclusive. An empty upper bound means that there is none.
+    ///
+    /// The bounds only hold if the characters after the prefix don't form a
+    /// contraction with its last character.
+    pub fn prefix_bounds(&self) -> (Vec<u8>, Vec<u8>) {
+        let mut lower = Vec::new();
+        for &weight in &self.primary {
+            push_weight(&mut lower, weight.into());
+        }
+        // The smallest byte string that is greater than all that start with
+        // the lower bound
+        let mut upper = lower.clone();
+        while let Some(byte) = upper.pop() {
+            if byte < u8::MAX {
+                upper.push(byte + 1);
+                break;
+            }
+        }
+        (lower, upper)
+    }
+
+    /// Returns a key that sorts in the reverse order, for descending sorts or
+    /// indexes on keys from [`SortKey::to_bytes`].
+    ///
+    /// Every weight is replaced by its complement and every level ends in the
+    /// highest weight, which sorts after the complements, so a key that is a
+    /// prefix of another one sorts last.
+    pub fn inverted(&self) -> SortKey {
+        let invert = |level: &[u16]| {
+            let mut weights: Vec<_> = level.iter().map(|&w| u16::MAX - w).collect();
+            weights.push(u16::MAX);
+            weights
+        };
+        SortKey {
+            primary: invert(&self.primary),
+            secondary: invert(&self.secondary),
+            case: invert(&self.case),
+            tertiary: invert(&self.tertiary),
+            quaternary: invert(&self.quaternary),
+        }
+    }
+
+    /// Returns [`SortKey::to_bytes`] as printable ASCII that still compares
+    /// like the key, for keys that have to be text, like in URLs.
+    ///
+    /// Every character holds six bits of the bytes, like in base64, but from
+    /// the alphabet `-0-9A-Z_a-z`, which is in ASCII order. The last character
+    /// is filled up with zero bits and there is no padding, so a key that is a
+    /// prefix of another one still sorts first.
+    pub fn to_ascii_string(&self) -> String {
+        let bytes = self.to_bytes();
+        let mut s = String::with_capacity((bytes.len() * 8).div_ceil(6));
+        let (mut bits, mut len) = (0u32, 0);
+        for byte in bytes {
+            bits = bits << 8 | u32::from(byte);
+            len += 8;
+            while len >= 6 {
+                len -= 6;
+                s.push(ASCII_DIGITS[(bits >> len) as usize & 0x3F] as char);
+            }
+        }
+        if len > 0 {
+            s.push(ASCII_DIGITS[(bits << (6 - len)) as usize & 0x3F] as char);
+        }
+        s
+    }
+
+    // The levels of the key, with the case level only if it has weights. The
+    // case level is empty in all keys of a collator without one, so leaving
+    // it out keeps the bytes and text of those keys the same.
+    fn levels(&self) -> impl Iterator<Item = &[u16]> {
+        let levels = [
+            &self.primary[..],
+            &self.secondary,
+            &self.case,
+            &self.tertiary,
+            &self.quaternary,
+        ];
+        let has_case = !self.case.is_empty();
+        IntoIterator::into_iter(levels)
+            .enumerate()
+            .filter(move |&(i, _)| i != 2 || has_case)
+            .map(|(_, level)| level)
+    }
+
+    // Calls `write` with every number that is written to the bytes of the
+    // key, in order, or with `None` for a level separator
+    fn encode(&self, mut write: impl FnMut(Option<u32>)) {
+        for (i, level) in self.levels().enumerate() {
+            if i > 0 {
+                write(None);
+            }
+            if i == 1 {
+                secondary_codes(level, |code| write(Some(code)));
+                continue;
+            }
+            for &weight in level {
+                write(Some(weight.into()));
+            }
+        }
+    }
+
     fn iter(&self) -> impl Iterator<Item = &u16> {
         self.primary
             .iter()
             .chain(std::iter::once(&0u16))
             .chain(self.secondary.iter())
             .chain(std::iter::once(&0u16))
+            .chain(self.case.iter())
+            .chain(std::iter::once(&0u16))
             .chain(self.tertiary.iter())
+            .chain(std::iter::once(&0u16))
+            .chain(self.quaternary.iter())
+    }
+}
+
+const LEVEL_SEPARATOR: u8 = 0x01;
+
+// The number of weights that fit in two digits with a first digit below `FF`
+const TWO_DIGIT_WEIGHTS: u32 = 253 * 254;
+
+// The digits of the ASCII form of keys, in ASCII order
+const ASCII_DIGITS: &[u8; 64] = b"-0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ_abcdefghijklmnopqrstuvwxyz";
+
+// The secondary weight of most characters, which is compressed in the bytes
+const COMMON_SECONDARY: u16 = 0x0020;
+
+// The number of weights that a run of common secondary weights is written
+// with, both for runs before a lower weight and for runs before a higher one
+const COMMON_CODES: u32 = 64;
+
+// Appends `weight` in base 254, with the digits shifted above the separator
+fn push_weight(bytes: &mut Vec<u8>, weight: u32) {
+    let digit = |d: u32| (d + 2) as u8;
+    if weight < TWO_DIGIT_WEIGHTS {
+        bytes.extend([digit(weight / 254), digit(weight % 254)]);
+    } else {
+        let weight = weight - TWO_DIGIT_WEIGHTS;
+        bytes.extend([0xFF, digit(weight / 254), digit(weight % 254)]);
+    }
+}
+
+// Writes the secondary weights with every run of the common weight as a code,
+// like in ICU. The codes sit between the weights below and above the
+// common weight, which are moved up to make room for them. A run that ends the
+// level or is followed by a lower weight gets a low code that grows with its
+// length, and a run followed by a higher weight a high code that shrinks with
+// its length, so that a shorter run sorts like the weight that follows it. A
+// run that is too long for one code starts with the codes that are furthest
+// from the ones of shorter runs.
+fn secondary_codes(weights: &[u16], mut write: impl FnMut(u32)) {
+    let low = u32::from(COMMON_SECONDARY);
+    let high = low + COMMON_CODES;
+    let max_run = COMMON_CODES as usize - 1;
+
+    let mut i = 0;
+    while i < weights.len() {
+        let weight = weights[i];
+        if weight != COMMON_SECONDARY {
+            let moved = if weight < COMMON_SECONDARY {
+                weight.into()
+            } else {
+                u32::from(weight) + 2 * COMMON_CODES - 1
+            };
+            write(moved);
+            i += 1;
+            continue;
+        }
+
+        let mut run = weights[i..]
+            .iter()
+            .take_while(|&&w| w == COMMON_SECONDARY)
+            .count();
+        i += run;
+        if weights.get(i).is_some_and(|&next| next > COMMON_SECONDARY) {
+            while run > max_run {
+                write(high);
+                run -= max_run;
+            }
+            write(high + COMMON_CODES - run as u32);
+        } else {
+            while run > max_run {
+                write(low + max_run as u32);
+                run -= max_run;
+            }
+            write(low + run as u32 - 1);
+        }
     }
 }
 
 impl PartialOrd for SortKey {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.iter().partial_cmp(other.iter())
+        Some(self.cmp(other))
     }
 }
 
@@ -159,6 +1211,38 @@ mod test {
     use super::*;
 
     #[test]
+    fn send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CollationElementTable>();
+        assert_send_sync::<Collator>();
+        assert_send_sync::<SortKey>();
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn shared_between_threads() {
+        use std::{sync::Arc, thread};
+
+        let table = Arc::new(CollationElementTable::default());
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let table = Arc::clone(&table);
+                thread::spawn(move || {
+                    let mut v = ["cáb", "b", "Cab", "a", "cab", "A"];
+                    v.sort_by_key(|s| table.generate_sort_key(s));
+                    v
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), ["a", "A", "b", "cab", "Cab", "cáb"]);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
     fn ascii_strings() {
         let table = CollationElementTable::default();
 
@@ -216,15 +1300,1091 @@ mod test {
     }
 
     #[test]
-    fn diacritics() {
+    #[cfg(feature = "bundled-ducet")]
+    fn ascii_fast_path() {
         let table = CollationElementTable::default();
+        let same_as_normalized = |s: &str| {
+            assert_eq!(
+                table.generate_sort_key(s),
+                table.generate_sort_key_from_chars(s.chars()),
+                "{:?}",
+                s
+            );
+        };
 
-        let mut v = ["cab", "dab", "Cab", "cáb"];
-        v.sort_by_key(|s| table.generate_sort_key(s));
+        // Including characters that form contractions with the last ASCII one
+        let ascii = (0..128u8).map(char::from);
+        for a in ascii.clone() {
+            for b in ascii.clone().chain(vec!['\u{301}', '\u{b7}', '\u{e9}']) {
+                same_as_normalized(&format!("{}{}", a, b));
+                same_as_normalized(&format!("x{}{}y", a, b));
+            }
+        }
+        same_as_normalized(&"The quick brown fox. ".repeat(10));
+
+        // Contractions of ASCII characters disable the fast path
+        let table = CollationElementTable::from(
+            "0061 ; [.0001.0020.0002]\n0062 ; [.0002.0020.0002]\n0061 0062 ; [.0003.0020.0002]\n",
+        )
+        .unwrap();
+        assert_eq!(table.ascii_prefix_len("abab"), 0);
+        assert_eq!(table.generate_sort_key("abab").primary, [3, 3]);
+    }
+
+    #[test]
+    fn ascii_runs() {
+        let s = "a".repeat(100);
+        assert_eq!(ascii_len(s.as_bytes()), 100);
+        assert_eq!(ascii_len(format!("{}\u{e9}a", s).as_bytes()), 100);
+        assert_eq!(ascii_len("\u{e9}".as_bytes()), 0);
+        assert_eq!(ascii_len(b""), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn compare_detailed() {
+        let table = CollationElementTable::default();
+        let key = |s| table.generate_sort_key(s);
+
+        assert_eq!(
+            key("a").compare_detailed(&key("b")),
+            (Ordering::Less, Some(Level::Primary))
+        );
+        assert_eq!(
+            key("á").compare_detailed(&key("a")),
+            (Ordering::Greater, Some(Level::Secondary))
+        );
+        assert_eq!(
+            key("a").compare_detailed(&key("A")),
+            (Ordering::Less, Some(Level::Tertiary))
+        );
+        assert_eq!(
+            key("a").compare_detailed(&key("a")),
+            (Ordering::Equal, None)
+        );
+
+        // The detailed comparison agrees with the regular one
+        for (a, b) in &[("ab", "a"), ("Ab", "ab"), ("áb", "ab"), ("b", "á")] {
+            assert_eq!(key(a).compare_detailed(&key(b)).0, key(a).cmp(&key(b)));
+        }
+    }
+
+    #[test]
+    fn custom_table() {
+        let table = CollationElementTable::from(
+            "0061 ; [.0001.0020.0002]\n0062 ; [.0002.0020.0002]\n0063 ; [.0003.0020.0002]\n",
+        )
+        .unwrap();
+        let collator = Collator::new(table.clone());
+        assert_eq!(collator.compare("ba", "ab"), Ordering::Greater);
+        assert_eq!(collator.sort_key("ba").primary, [2, 1]);
+
+        let collator = Collator::with_tailoring(table, "&a < c").unwrap();
+        let mut v = ["b", "c", "a"];
+        v.sort_by(|a, b| collator.compare(a, b));
+        assert_eq!(v, ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn merge_tables() {
+        let mut table =
+            CollationElementTable::from("0061 ; [.0001.0020.0002]\n0062 ; [.0002.0020.0002]\n")
+                .unwrap();
+        table
+            .extend_from_str(
+                "0062 ; [.0003.0020.0002]\nE000 ; [.0004.0020.0002]\nE000 E001 ; [.0005.0020.0002]\n",
+            )
+            .unwrap();
+        assert!(table.extend_from_str("0063 ; [.0005.0020]\n").is_err());
+
+        assert_eq!(table.generate_sort_key("a").primary, [1]);
+        assert_eq!(table.generate_sort_key("b").primary, [3]);
+        assert_eq!(table.generate_sort_key("\u{e000}\u{e001}").primary, [5]);
+        assert!(!table.contains_key("c"));
+        assert_eq!(table.max_contraction_len, 2);
+    }
+
+    #[test]
+    fn allkeys_round_trip() {
+        let s = "@version 13.0.0\n\
+                 @implicitweights 17000..18AFF; FB00\n\
+                 002D ; [*020D.0020.0002]\n\
+                 0061 ; [.1FA2.0020.0002]\n\
+                 0061 0301 ; [.1FA2.0020.0002][.0000.0024.0002]\n\
+                 00E6 ; [.1FA2.0020.0004][.0000.0110.0004][.1FFE.0020.0004]\n";
+        let table = CollationElementTable::from(s).unwrap();
+        assert_eq!(table.to_allkeys_string(), s);
+        assert_eq!(
+            CollationElementTable::from(&table.to_allkeys_string()).unwrap(),
+            table
+        );
+    }
+
+    #[test]
+    fn header() {
+        let table = CollationElementTable::from(
+            "@version 15.1.0 # comment\n\
+             @implicitweights 17000..18AFF; FB00\n\
+             @implicitweights 1B170..1B2FF; FB01\n\
+             0061 ; [.1FA2.0020.0002]\n",
+        )
+        .unwrap();
+        let header = table.header();
+        assert_eq!(header.version(), Some("15.1.0"));
+        assert_eq!(header.unicode_version, Some((15, 1)));
+        assert_eq!(
+            header.implicit_weights(),
+            [
+                ImplicitRange {
+                    range: 0x17000..=0x18AFF,
+                    base: 0xFB00
+                },
+                ImplicitRange {
+                    range: 0x1B170..=0x1B2FF,
+                    base: 0xFB01
+                }
+            ]
+        );
+
+        let table = CollationElementTable::from("0061 ; [.1FA2.0020.0002]\n").unwrap();
+        assert_eq!(*table.header(), TableHeader::default());
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn bundled_header() {
+        let table = CollationElementTable::default();
+        assert!(!table.header().version().unwrap().is_empty());
+        assert!(!table.header().implicit_weights().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn elements_match_table() {
+        let table = CollationElementTable::default();
+        // Expansions, implicit weights and characters that decompose, but no
+        // contractions, so each character can be looked up on its own
+        for s in &[
+            "Æsir",
+            "Größe",
+            "ﬁ漢\u{10FFFF}",
+            "x\u{301}\u{327}",
+            "",
+            "abc",
+        ] {
+            let expected: Vec<_> = s
+                .nfd()
+                .flat_map(|c| match table.get(&c.to_string()) {
+                    Some(elements) => elements.to_vec(),
+                    None => table.implicit_elements(c),
+                })
+                .collect();
+            let elements: Vec<_> = CollationElements::from_str(&table, s).collect();
+            assert_eq!(elements, expected, "{:?}", s);
+            assert_eq!(
+                CollationElements::from(&table, s.chars()).collect::<Vec<_>>(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn weights_of() {
+        let table = CollationElementTable::default();
+        let elem = |primary, secondary, tertiary: u16| CollationElement {
+            variable: false,
+            tertiary_class: tertiary as u8,
+            primary,
+            secondary,
+            tertiary,
+        };
+        assert_eq!(table.weights_of('a'), Some(&[elem(0x1FA2, 0x20, 0x02)][..]));
+        assert_eq!(table.weights_of('A'), Some(&[elem(0x1FA2, 0x20, 0x08)][..]));
+        assert_eq!(table.weights_of('\u{e6}').map(<[_]>::len), Some(3));
+        // Implicit weights and unassigned code points
+        assert_eq!(table.weights_of('\u{4e00}'), None);
+        assert_eq!(table.weights_of('\u{10FFFF}'), None);
+    }
+
+    #[test]
+    fn compact_elements() {
+        // A single element fits in the space of the pointer to the others
+        assert_eq!(
+            std::mem::size_of::<Elements>(),
+            std::mem::size_of::<Box<[CollationElement]>>()
+        );
+
+        let table = CollationElementTable::from(
+            "0061 ; [.1FA2.0020.0002]\n\
+             00E6 ; [.1FA2.0020.0004][.0000.0110.0004][.1FFE.0020.0004]\n",
+        )
+        .unwrap();
+        assert_eq!(table["a"].len(), 1);
+        assert_eq!(table["\u{e6}"].len(), 3);
+        // Entries are only seen as slices
+        assert_eq!(table.get("a"), Some(&table["a"]));
+        assert_eq!(table.get("b"), None);
+        assert!(table.contains_key("\u{e6}") && !table.contains_key("b"));
+        assert_eq!((table.len(), table.is_empty()), (2, false));
+        let keys: Vec<_> = table
+            .iter()
+            .map(|(key, elements)| (key, elements.len()))
+            .collect();
+        assert_eq!(keys, [("a", 1), ("\u{e6}", 3)]);
+
+        let key = table.generate_sort_key("\u{e6}a");
+        assert_eq!(key.primary, [0x1FA2, 0x1FFE, 0x1FA2]);
+        assert_eq!(key.secondary, [0x20, 0x110, 0x20, 0x20]);
+        assert_eq!(key.tertiary, [4, 4, 4, 2]);
+    }
+
+    #[test]
+    fn lenient_parsing() {
+        let s = "# A table\n\
+                 0061 ; [.0001.0020.0002] # a\n\
+                 0062 ; [.0002.0020]\n\
+                 0061 0065 ; [.0003.0020.0002]\n\
+                 0065 ; [.0004.0020.0002]";
+        assert!(CollationElementTable::from(s).is_err());
+
+        let (table, warnings) = CollationElementTable::from_lenient(s);
+        assert_eq!(
+            warnings,
+            [ParseWarning {
+                line: 3,
+                content: "0062 ; [.0002.0020]".to_owned()
+            }]
+        );
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.max_contraction_len, 2);
+        assert_eq!(table.generate_sort_key("aee").primary, [3, 4]);
+    }
+
+    #[test]
+    fn surrogates_and_noncharacters() {
+        let table = CollationElementTable::from(
+            "0061 ; [.0001.0020.0002]\n\
+             FFFF ; [.FFFF.0000.0000]\n\
+             D800 ; [.0002.0020.0002]\n\
+             0061 DFFF ; [.0003.0020.0002]\n",
+        )
+        .unwrap();
+
+        // Rows with surrogates are skipped, noncharacters are kept
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.generate_sort_key("\u{ffff}").primary, [0xFFFF]);
+        assert_eq!(table.max_contraction_len, 1);
+    }
+
+    #[test]
+    fn surrogate_warnings() {
+        let s = "0061 ; [.0001.0020.0002]\n\
+                 D800 ; [.0002.0020.0002] # a surrogate\n\
+                 \n\
+                 0061 DFFF ; [.0003.0020.0002]";
+        let expected = [
+            ParseWarning {
+                line: 2,
+                content: "D800 ; [.0002.0020.0002] # a surrogate".to_owned(),
+            },
+            ParseWarning {
+                line: 4,
+                content: "0061 DFFF ; [.0003.0020.0002]".to_owned(),
+            },
+        ];
+
+        let (table, warnings) = CollationElementTable::from_with_warnings(s).unwrap();
+        assert_eq!(warnings, expected);
+        assert_eq!(table.len(), 1);
+        let (table, warnings) = CollationElementTable::from_lenient(s);
+        assert_eq!(warnings, expected);
+        assert_eq!(table.len(), 1);
+        assert_eq!(
+            warnings[0].to_string(),
+            "skipped invalid line 2: \"D800 ; [.0002.0020.0002] # a surrogate\""
+        );
+    }
+
+    #[test]
+    fn normalize() {
+        let table = CollationElementTable::from(
+            "0065 ; [.0001.0020.0002]\n0301 ; [.0000.0024.0002]\n0065 0301 ; [.0002.0020.0002]\n",
+        )
+        .unwrap();
+        let normalized = table.normalize("caf\u{e9}");
+        assert_eq!(normalized, "cafe\u{301}");
+        assert_eq!(
+            table.generate_sort_key(&normalized),
+            table.generate_sort_key("caf\u{e9}")
+        );
+        assert_eq!(table.normalize("\u{e9}"), "e\u{301}");
+    }
+
+    #[test]
+    fn contraction_lookahead_limit() {
+        let table = CollationElementTable::from(
+            "0061 ; [.0001.0020.0002]\n0061 0061 ; [.0002.0020.0002]\n",
+        )
+        .unwrap();
+        assert_eq!(table.max_contraction_len, 2);
+
+        // A long run of the same character is split into pairs
+        let key = table.generate_sort_key(&"a".repeat(100_001));
+        assert_eq!(key.primary.len(), 50_001);
+        assert_eq!(key.primary[..2], [2, 2]);
+        assert_eq!(key.primary.last(), Some(&1));
+
+        // Without probing further ahead than the longest key, so the first
+        // pair is looked up after only reading the pair
+        let read = std::cell::Cell::new(0);
+        let chars = std::iter::repeat('a').inspect(|_| read.set(read.get() + 1));
+        let mut elements = CollationElements::from(&table, chars);
+        assert_eq!(elements.next().map(|e| e.primary), Some(2));
+        assert_eq!(read.get(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn filtered_table() {
+        let full = CollationElementTable::default();
+        let latin = CollationElementTable::from_filtered(DUCET, |c| {
+            c.is_ascii() || ('\u{c0}'..='\u{24f}').contains(&c)
+        })
+        .unwrap();
+        assert!(latin.len() * 10 < full.len());
+        assert!(!latin.contains_key("\u{3b1}"));
+        assert!(latin.contains_key("\u{301}"));
+        assert_eq!(latin.header(), full.header());
+
+        let mut words = [
+            "Zoe",
+            "zo\u{eb}",
+            "\u{c9}mile",
+            "emile",
+            "\u{f8}re",
+            "ore",
+            "\u{df}",
+            "ss",
+            "Stra\u{df}e",
+            "co-op",
+            "coop",
+            "\u{141}\u{f3}d\u{17a}",
+            "Lodz",
+            "a b",
+            "10",
+            "9",
+        ];
+        words.sort_by_key(|s| full.generate_sort_key(s));
+        for (a, b) in words.iter().zip(&words[1..]) {
+            assert_eq!(
+                latin.generate_sort_key(a).cmp(&latin.generate_sort_key(b)),
+                full.generate_sort_key(a).cmp(&full.generate_sort_key(b)),
+                "{} {}",
+                a,
+                b
+            );
+        }
+        // Other scripts sort after the kept ones
+        assert!(latin.generate_sort_key("\u{3b1}") > latin.generate_sort_key("z"));
+    }
+
+    #[test]
+    fn try_generate_sort_key() {
+        let table =
+            CollationElementTable::from("0061 ; [.0001.0020.0002]\n0301 ; [.0000.0021.0002]\n")
+                .unwrap();
+        assert_eq!(
+            table.try_generate_sort_key("a\u{e1}"),
+            Ok(table.generate_sort_key("a\u{e1}"))
+        );
+        // Ideographs have implicit weights without being in the table
+        assert!(table.try_generate_sort_key("a\u{4e00}\u{fffd}").is_ok());
+
+        let err = table.try_generate_sort_key("ab\u{e9}").unwrap_err();
+        assert_eq!(err, CollationError::MissingChar('b'));
+        assert_eq!(err.to_string(), "no collation elements for 'b' (U+0062)");
+        assert_eq!(
+            table.try_generate_sort_key("\u{e9}"),
+            Err(CollationError::MissingChar('e'))
+        );
+    }
+
+    #[test]
+    fn contraction_gaps() {
+        let mut table = CollationElementTable::from(
+            "0061 ; [.0001.0020.0002]\n\
+             0062 ; [.0002.0020.0002]\n\
+             0063 ; [.0003.0020.0002]\n\
+             0064 ; [.0004.0020.0002]\n\
+             0061 0062 ; [.0005.0020.0002]\n\
+             0061 0062 0063 0064 ; [.0006.0020.0002]\n",
+        )
+        .unwrap();
+        let primary = |table: &CollationElementTable, s| table.generate_sort_key(s).primary;
+
+        // The longest key wins, even though `abc` is not a key
+        assert_eq!(primary(&table, "abcd"), [6]);
+        assert_eq!(primary(&table, "abcdab"), [6, 5]);
+        // Without it, the characters of the gap are looked up again
+        assert_eq!(primary(&table, "abc"), [5, 3]);
+        assert_eq!(primary(&table, "abcab"), [5, 3, 5]);
+        assert_eq!(primary(&table, "abcabcd"), [5, 3, 6]);
+
+        // Keys that are added later fill and create gaps as well
+        table
+            .extend_from_str(
+                "0061 0062 0063 ; [.0007.0020.0002]\n0063 0061 0061 0061 ; [.0008.0020.0002]\n",
+            )
+            .unwrap();
+        assert_eq!(primary(&table, "abc"), [7]);
+        assert_eq!(primary(&table, "abcd"), [6]);
+        assert_eq!(primary(&table, "caaab"), [8, 2]);
+        assert_eq!(primary(&table, "caab"), [3, 1, 5]);
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn control_characters() {
+        let collator = Collator::default();
+        let strings = ["a\0b", "ab", "a\tb", "\0", "", "a\u{1}\0", "a\nb", "A\0B"];
+        let keys: Vec<_> = strings.iter().map(|s| collator.sort_key(s)).collect();
+
+        // NUL is completely ignorable, but doesn't cut off the string
+        assert_eq!(keys[0], keys[1]);
+        assert_eq!(keys[0].primary.len(), 2);
+        assert_eq!(
+            collator.distinguishing_strength("a\0b", "ab"),
+            Some(Strength::Identical)
+        );
+        assert_eq!(collator.compare("a\0b", "ab"), Ordering::Equal);
+        assert_eq!(collator.total_cmp("a\0b", "ab"), Ordering::Less);
+
+        // Ignorable weights are left out, so they can't be confused with the
+        // level separators
+        for (key, s) in keys.iter().zip(&strings) {
+            assert_eq!(collator.sort_key(s), *key);
+            for level in &[&key.primary, &key.secondary, &key.tertiary, &key.quaternary] {
+                assert!(!level.contains(&0));
+            }
+        }
+        for a in &keys {
+            for b in &keys {
+                assert_eq!(a.to_bytes().cmp(&b.to_bytes()), a.cmp(b));
+            }
+        }
+    }
+
+    #[test]
+    fn element_order() {
+        let elem = |variable, primary, secondary, tertiary: u16| CollationElement {
+            variable,
+            tertiary_class: tertiary as u8,
+            primary,
+            secondary,
+            tertiary,
+        };
+        // Ordered by weights, not by the variable flag
+        assert!(elem(true, 0x0209, 0x20, 0x02) < elem(false, 0x1FA2, 0x20, 0x02));
+        assert!(elem(false, 0, 0x20, 0x02) < elem(false, 0, 0x21, 0x02));
+        assert!(elem(false, 0x0100, 0x20, 0x08) < elem(false, 0x0100, 0x21, 0x02));
+        assert!(elem(false, 0x0100, 0x20, 0x02) < elem(true, 0x0100, 0x20, 0x02));
+        assert_ne!(
+            elem(false, 0x0100, 0x20, 0x02),
+            elem(true, 0x0100, 0x20, 0x02)
+        );
+
+        let mut elements = [
+            elem(false, 0x1FA2, 0x20, 0x02),
+            elem(true, 0x0209, 0x20, 0x02),
+        ];
+        elements.sort();
+        assert_eq!(elements[0].primary, 0x0209);
+    }
+
+    #[test]
+    fn byte_encoding() {
+        let weights = [
+            1, 2, 253, 254, 255, 0x100, 0xFB05, 0xFB06, 0xFB07, 0xFFFE, 0xFFFF,
+        ];
+        let mut keys = Vec::new();
+        for &w in &weights {
+            keys.push(SortKey {
+                primary: vec![w],
+                secondary: vec![0x20],
+                tertiary: vec![2, w],
+                quaternary: vec![0xFFFF],
+                ..SortKey::new()
+            });
+            keys.push(SortKey {
+                primary: vec![w, 1],
+                ..SortKey::new()
+            });
+        }
+        keys.push(SortKey::new());
+        for a in &keys {
+            let bytes = a.to_bytes();
+            assert!(!bytes.contains(&0));
+            assert_eq!(bytes.iter().filter(|&&b| b == LEVEL_SEPARATOR).count(), 3);
+            for b in &keys {
+                assert_eq!(bytes.cmp(&b.to_bytes()), a.cmp(b), "{:?} {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn secondary_compression() {
+        let key = |secondary: Vec<u16>| SortKey {
+            secondary,
+            ..SortKey::new()
+        };
+        let (common, run) = (COMMON_SECONDARY, COMMON_CODES as usize - 1);
+
+        // Every sequence of up to five weights around the common one
+        let around = [1, common - 1, common, common + 1, 0xFFFF];
+        let mut keys = vec![key(vec![])];
+        for len in 1..=5 {
+            for mut n in 0..around.len().pow(len) {
+                let mut weights = Vec::new();
+                for _ in 0..len {
+                    weights.push(around[n % around.len()]);
+                    n /= around.len();
+                }
+                keys.push(key(weights));
+            }
+        }
+        // Runs around the length that fits in one code, alone and before
+        // lower and higher weights
+        for len in &[1, run - 1, run, run + 1, 2 * run, 2 * run + 1, 3 * run] {
+            for next in &[None, Some(common - 1), Some(common + 1)] {
+                for start in &[None, Some(common + 1)] {
+                    let mut weights: Vec<_> = start.iter().copied().collect();
+                    weights.extend(vec![common; *len]);
+                    weights.extend(next);
+                    keys.push(key(weights));
+                }
+            }
+        }
+
+        for a in &keys {
+            let bytes = a.to_bytes();
+            assert!(!bytes.contains(&0));
+            for b in &keys {
+                assert_eq!(bytes.cmp(&b.to_bytes()), a.cmp(b), "{:?} {:?}", a, b);
+            }
+        }
+
+        // A run is written as one weight as long as it fits in a code
+        assert_eq!(key(vec![common; run]).to_bytes().len(), 3 + 2);
+        assert_eq!(key(vec![common; run + 1]).to_bytes().len(), 3 + 4);
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn byte_len() {
+        let mut keys = vec![
+            SortKey::new(),
+            SortKey {
+                primary: vec![1, 0xFB05, 0xFFFF],
+                secondary: vec![0x20; 200],
+                case: vec![1, 2],
+                tertiary: vec![2, 0xFFFE],
+                quaternary: vec![0xFFFF],
+            },
+            SortKey {
+                secondary: vec![0x20, 0x21, 0x1F, 0x20, 0x20, 0xFFFF],
+                ..SortKey::new()
+            },
+        ];
+        let mut collator = Collator::default();
+        let strings = [
+            "",
+            "abc",
+            "R\u{e9}sum\u{e9}",
+            "\u{1ea1}\u{301} \u{3042}",
+            "\u{4e00}\u{10ffff}",
+            "long long long long long long long long long long long long text",
+        ];
+        for &weighting in &[VariableWeighting::NonIgnorable, VariableWeighting::Shifted] {
+            collator.set_variable_weighting(weighting);
+            for &case_level in &[false, true] {
+                collator.set_case_level(case_level);
+                keys.extend(strings.iter().map(|s| collator.sort_key(s)));
+            }
+        }
+        for key in keys
+            .iter()
+            .flat_map(|key| vec![key.clone(), key.inverted()])
+        {
+            assert_eq!(key.byte_len(), key.to_bytes().len(), "{:?}", key);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn prefix_bounds() {
+        let collator = Collator::default();
+        let (lower, upper) = collator.sort_key("Sch").prefix_bounds();
+        let names = [
+            "Sch",
+            "Schmidt",
+            "schulz",
+            "Sch\u{e4}fer",
+            "SCHOLZ",
+            "Sch z",
+            "Sc",
+            "Scott",
+            "Sbx",
+            "Sci",
+            "Sd",
+            "Smith",
+            "Szabo",
+            "Bach",
+            "",
+            "Zeiss",
+        ];
+        for name in &names {
+            let bytes = collator.sort_key(name).to_bytes();
+            assert_eq!(
+                lower <= bytes && bytes < upper,
+                name.to_lowercase().starts_with("sch"),
+                "{}",
+                name
+            );
+        }
+
+        // The upper bound carries over bytes that can't be incremented
+        let key = SortKey {
+            primary: vec![0x0100, 253],
+            ..SortKey::new()
+        };
+        let (lower, upper) = key.prefix_bounds();
+        assert_eq!(lower, [0x03, 0x04, 0x02, 0xFF]);
+        assert_eq!(upper, [0x03, 0x04, 0x03]);
+        assert_eq!(SortKey::new().prefix_bounds(), (vec![], vec![]));
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn inverted() {
+        let collator = Collator::default();
+        let names = [
+            "target",
+            "Cargo.lock",
+            "docs",
+            "README.md",
+            "Cargo.toml",
+            "LICENSE",
+            "benches",
+            "CONTRIBUTING.md",
+            "util",
+            "build.rs",
+            "doc",
+            "",
+            "tests",
+            "src",
+            "examples",
+        ];
+        let mut ascending = names;
+        ascending.sort_by_key(|s| collator.sort_key(s));
+        let mut descending = names;
+        descending.sort_by_key(|s| collator.sort_key(s).inverted());
+        ascending.reverse();
+        assert_eq!(descending, ascending);
+
+        let mut descending = names;
+        descending.sort_by_key(|s| collator.sort_key(s).inverted().to_bytes());
+        assert_eq!(descending, ascending);
+
+        // Prefixes sort last, also when the keys differ at a later level
+        let key = |s| collator.sort_key(s).inverted();
+        assert!(key("doc") > key("docs"));
+        assert!(key("") > key("a"));
+        assert!(key("a") > key("A"));
+        assert!(key("a") > key("\u{e1}"));
+        assert_eq!(key("a"), key("a"));
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn ascii_keys() {
+        assert!(ASCII_DIGITS.windows(2).all(|w| w[0] < w[1]));
+
+        let collator = Collator::default();
+        let words = [
+            "",
+            "a",
+            "A",
+            "ab",
+            "a b",
+            "a-b",
+            "\u{e1}",
+            "a\u{301}b",
+            "b",
+            "abc",
+            "abcd",
+            "abcde",
+            "co-op",
+            "coop",
+            "CO-OP",
+            "\u{3b1}",
+            "\u{430}",
+            "\u{5d0}",
+            "1",
+            "10",
+            "9",
+            "\u{4e00}",
+            "\u{fffd}",
+        ];
+        // Pairs of words and their concatenations, which share prefixes
+        let mut strings = Vec::new();
+        for a in &words {
+            for b in &words {
+                strings.push(format!("{}{}", a, b));
+            }
+        }
+        let keys: Vec<_> = strings.iter().map(|s| collator.sort_key(s)).collect();
+        let ascii: Vec<_> = keys.iter().map(SortKey::to_ascii_string).collect();
+        for (key, s) in keys.iter().zip(&ascii) {
+            assert!(s.bytes().all(|b| b.is_ascii_graphic()));
+            assert_eq!(s.len(), (key.to_bytes().len() * 8).div_ceil(6));
+        }
+        for i in 0..keys.len() {
+            for j in 0..keys.len() {
+                assert_eq!(
+                    ascii[i].cmp(&ascii[j]),
+                    keys[i].cmp(&keys[j]),
+                    "{:?} {:?}",
+                    strings[i],
+                    strings[j]
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn ignorables_in_bytes() {
+        let collator = Collator::default();
+        // Completely ignorable, secondary and tertiary only
+        for s in &["a\u{0}", "a\u{301}", "a\u{1d165}", "\u{301}", ""] {
+            let bytes = collator.sort_key(s).to_bytes();
+            assert!(!bytes.contains(&0));
+            assert_eq!(bytes.iter().filter(|&&b| b == LEVEL_SEPARATOR).count(), 3);
+        }
+        let key = |s| collator.sort_key(s).to_bytes();
+        assert!(key("a") < key("a\u{301}"));
+        assert!(key("\u{301}") < key("a"));
+        assert_eq!(key("a\u{0}"), key("a"));
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn default_ignorables() {
+        let table = CollationElementTable::default();
+        // ZWJ, ZWNJ, a variation selector and a tag character
+        for &c in &["\u{200d}", "\u{200c}", "\u{fe0f}", "\u{e0041}"] {
+            assert!(table[c]
+                .iter()
+                .all(|e| e.primary == 0 && e.secondary == 0 && e.tertiary == 0));
+        }
+
+        assert_eq!(
+            table.generate_sort_key("a\u{200d}b"),
+            table.generate_sort_key("ab")
+        );
+        assert_eq!(
+            table.generate_sort_key("\u{4e00}\u{fe00}"),
+            table.generate_sort_key("\u{4e00}")
+        );
+
+        // But they still block contractions, as they are starters
+        let primary = |s| table.generate_sort_key(s).primary;
+        assert_eq!(primary("\u{438}\u{200d}\u{306}"), primary("\u{438}"));
+        assert_ne!(primary("\u{438}\u{306}"), primary("\u{438}"));
+        assert_eq!(
+            primary("l\u{200d}\u{b7}"),
+            [primary("l"), primary("\u{b7}")].concat()
+        );
+        assert_ne!(primary("l\u{200d}\u{b7}"), primary("l\u{b7}"));
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn canonical_ordering() {
+        let table = CollationElementTable::default();
+        // Dot below (class 220) sorts before acute (230) and cedilla (202)
+        // before both, in whatever order they are typed
+        let marks = ["\u{301}", "\u{323}", "\u{327}"];
+        let canonical = "a\u{327}\u{323}\u{301}";
+        for a in &marks {
+            for b in marks.iter().filter(|b| b != &a) {
+                for c in marks.iter().filter(|c| c != &a && c != &b) {
+                    let s = format!("a{}{}{}", a, b, c);
+                    assert_eq!(table.normalize(&s), canonical);
+                    assert_eq!(
+                        table.generate_sort_key(&s),
+                        table.generate_sort_key(canonical)
+                    );
+                }
+            }
+        }
+        assert_eq!(
+            table.generate_sort_key("\u{1ec7}"),
+            table.generate_sort_key("e\u{302}\u{323}")
+        );
+
+        // Marks of the same class are not reordered, so they stay distinct
+        assert_ne!(
+            table.generate_sort_key("a\u{301}\u{300}"),
+            table.generate_sort_key("a\u{300}\u{301}")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn marks_after_a_starter() {
+        let table = CollationElementTable::default();
+        let secondary = |s: &str| table.generate_sort_key(s).secondary;
+        // The weights of the marks follow the one of the letter, in canonical
+        // order
+        let expected = [
+            secondary("a")[0],
+            secondary("\u{323}")[0],
+            secondary("\u{301}")[0],
+        ];
+        assert_eq!(secondary("a\u{301}\u{323}"), expected);
+        assert_eq!(secondary("a\u{323}\u{301}"), expected);
+        assert_eq!(secondary("\u{e1}\u{323}"), expected);
+        assert_eq!(secondary("\u{1ea1}\u{301}"), expected);
+        assert_eq!(
+            table.generate_sort_key("a\u{301}\u{323}").primary,
+            table.generate_sort_key("a").primary
+        );
+
+        // A contraction of the letter with the second mark is still found,
+        // and the first mark follows it
+        let short_i = table.generate_sort_key("\u{419}");
+        let key = table.generate_sort_key("\u{418}\u{323}\u{306}");
+        assert_eq!(key.primary, short_i.primary);
+        assert_eq!(
+            key.secondary,
+            [short_i.secondary[0], secondary("\u{323}")[0]]
+        );
+        assert_eq!(key, table.generate_sort_key("\u{419}\u{323}"));
+
+        // But not past a mark of the same class
+        let key = table.generate_sort_key("\u{418}\u{301}\u{306}");
+        assert_eq!(key.primary, table.generate_sort_key("\u{418}").primary);
+    }
+
+    #[test]
+    fn long_mark_runs() {
+        let table = CollationElementTable::from(
+            "0061 ; [.0001.0020.0002]\n\
+             0301 ; [.0000.0024.0002]\n\
+             0323 ; [.0000.0025.0002]\n\
+             0061 0301 ; [.0002.0020.0002]\n",
+        )
+        .unwrap();
+        assert_eq!(table.generate_sort_key("a\u{323}\u{301}").primary, [2]);
+
+        // Looking for a mark that completes a contraction doesn't scan the
+        // whole run again for every mark, which would take quadratic time
+        for mark in &["\u{301}", "\u{323}"] {
+            let s = format!("a{}", mark.repeat(100_000));
+            let key = table.generate_sort_key(&s);
+            assert_eq!(key.primary.len(), 1);
+            assert_eq!(key.secondary.len(), 100_001 - (*mark == "\u{301}") as usize);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn nfc_and_nfd_input() {
+        let table = CollationElementTable::default();
+
+        // "é" as U+00E9 and as U+0065 U+0301
+        assert_eq!(
+            table.generate_sort_key("\u{e9}"),
+            table.generate_sort_key("e\u{301}")
+        );
+        assert_eq!(
+            table.generate_sort_key("caf\u{e9}s"),
+            table.generate_sort_key("cafe\u{301}s")
+        );
+        assert_eq!(
+            table.generate_sort_key("\u{1ec7}"),
+            table.generate_sort_key("e\u{323}\u{302}")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn canonical_equivalence() {
+        let table = CollationElementTable::default();
+
+        let equivalent: &[&[&str]] = &[
+            // Angstrom sign, precomposed and decomposed A with ring above
+            &["\u{212b}", "\u{c5}", "A\u{30a}"],
+            // Ohm sign and capital omega
+            &["\u{2126}", "\u{3a9}"],
+            // Marks with different combining classes in either order
+            &[
+                "a\u{301}\u{323}",
+                "a\u{323}\u{301}",
+                "\u{e1}\u{323}",
+                "\u{1ea1}\u{301}",
+            ],
+            &["q\u{307}\u{323}", "q\u{323}\u{307}"],
+            // A contraction (И + breve) interrupted by a mark that reorders
+            &["\u{419}", "\u{418}\u{306}"],
+            &[
+                "\u{419}\u{323}",
+                "\u{418}\u{306}\u{323}",
+                "\u{418}\u{323}\u{306}",
+            ],
+            // Hangul syllable and conjoining jamo
+            &["\u{d55c}", "\u{1112}\u{1161}\u{11ab}"],
+        ];
+
+        for group in equivalent {
+            let expected = table.generate_sort_key(group[0]);
+            assert!(!expected.primary.is_empty());
+            for s in &group[1..] {
+                assert_eq!(table.generate_sort_key(s), expected, "{:?}", s);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn from_path() {
+        // Unique to the process, so that parallel test runs don't collide
+        let path =
+            std::env::temp_dir().join(format!("collate_from_path_{}.txt", std::process::id()));
+
+        std::fs::write(
+            &path,
+            "0061 ; [.0001.0020.0002]\n0062 ; [.0002.0020.0002]\n",
+        )
+        .unwrap();
+        let table = CollationElementTable::from_path(&path).unwrap();
+        assert_eq!(table.generate_sort_key("ba").primary, [2, 1]);
+
+        std::fs::write(&path, "0061 ; [.0001.0020.0002]\n0062 ; [.0002.0020]\n").unwrap();
+        assert!(matches!(
+            CollationElementTable::from_path(&path),
+            Err(Error::Table { line: 2 })
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            CollationElementTable::from_path(&path),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn sort_key_from_chars() {
+        let table = CollationElementTable::default();
+
+        for s in &[
+            "",
+            "a",
+            "Cargo.toml",
+            "caf\u{e9}",
+            "\u{419}",
+            "a\u{323}\u{301}",
+        ] {
+            assert_eq!(
+                table.generate_sort_key_from_chars(s.chars()),
+                table.generate_sort_key(s)
+            );
+        }
+
+        // Decomposed characters, with the combining marks out of order
+        let chars = ['c', 'a', 'f', 'e', '\u{301}', '\u{323}'];
+        assert_eq!(
+            table.generate_sort_key_from_chars(chars.iter().copied()),
+            table.generate_sort_key("caf\u{1eb9}\u{301}")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn diacritics() {
+        let table = CollationElementTable::default();
+
+        let mut v = ["cab", "dab", "Cab", "cáb"];
+        v.sort_by_key(|s| table.generate_sort_key(s));
         assert_eq!(v, ["cab", "Cab", "cáb", "dab"]);
 
         let mut v = ["e", "A", "á", "a", "E", "Á", "é", "É"];
         v.sort_by_key(|s| table.generate_sort_key(s));
         assert_eq!(v, ["a", "A", "á", "Á", "e", "E", "é", "É"]);
     }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn prevowel_reordering() {
+        let table = CollationElementTable::default();
+
+        // Thai and Lao prevowels are written before the consonant but sort
+        // after it, which the contractions in the table take care of
+        let mut v = ["\u{e02}", "\u{e40}\u{e01}", "\u{e01}\u{e32}"];
+        v.sort_by_key(|s| table.generate_sort_key(s));
+        assert_eq!(v, ["\u{e01}\u{e32}", "\u{e40}\u{e01}", "\u{e02}"]);
+
+        let mut v = ["\u{e82}", "\u{ec0}\u{e81}", "\u{e81}\u{eb2}"];
+        v.sort_by_key(|s| table.generate_sort_key(s));
+        assert_eq!(v, ["\u{e81}\u{eb2}", "\u{ec0}\u{e81}", "\u{e82}"]);
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn expansions_and_contractions() {
+        let table = CollationElementTable::default();
+        let sorted = |mut v: Vec<&'static str>| {
+            v.sort_by_key(|s| table.generate_sort_key(s));
+            v
+        };
+
+        // Ligatures expand to their letters and sort right after them
+        assert_eq!(
+            sorted(vec!["fj", "\u{fb01}", "fi", "fh"]),
+            ["fh", "fi", "\u{fb01}", "fj"]
+        );
+        assert_eq!(
+            sorted(vec!["st", "\u{df}", "ss", "sr"]),
+            ["sr", "ss", "\u{df}", "st"]
+        );
+        assert_eq!(
+            sorted(vec!["af", "\u{e6}", "ae", "ad"]),
+            ["ad", "ae", "\u{e6}", "af"]
+        );
+
+        // Contractions map several characters to a single letter
+        assert_eq!(
+            table.generate_sort_key("\u{438}\u{306}"),
+            table.generate_sort_key("\u{439}")
+        );
+        assert_eq!(
+            sorted(vec!["\u{439}", "\u{438}\u{44f}", "\u{43a}"]),
+            ["\u{438}\u{44f}", "\u{439}", "\u{43a}"]
+        );
+        assert_eq!(table.generate_sort_key("l\u{b7}").primary.len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn contractions() {
+        let table = CollationElementTable::default();
+        let keys: Vec<_> = table.contractions().map(|(k, _)| k).collect();
+        assert!(keys.contains(&"\u{438}\u{306}"));
+        assert!(keys.iter().all(|k| k.chars().count() > 1));
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        assert!(table.contractions().count() < table.len());
+    }
 }
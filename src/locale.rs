@@ -1,14 +1,36 @@
 use crate::{
     collation_rules::{self, Collation},
-    ldml::LDML,
+    collator::root_table,
+    ldml::Ldml,
+    CollationElementTable, Error,
 };
-use std::convert::TryFrom;
+use std::{borrow::Cow, collections::BTreeMap, convert::TryFrom, sync::Mutex};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
 use strong_xml::XmlRead;
 
+// The locales with collation data that is compiled into the crate
+const BUNDLED: &[(&str, &str)] = &[
+    ("de", include_str!("../locales/de.xml")),
+    ("es", include_str!("../locales/es.xml")),
+];
+
+// Tailored tables and their settings by bundled language and collation type,
+// so that each is only built once. Like the root table, the tables are kept
+// for the rest of the program and shared by all collators using them, and
+// there are only as many as there are bundled collations.
+#[allow(clippy::type_complexity)]
+static TABLES: Mutex<
+    BTreeMap<(&'static str, String), (&'static CollationElementTable, Vec<(String, String)>)>,
+> = Mutex::new(BTreeMap::new());
+
+pub(crate) type Tailored = (Cow<'static, CollationElementTable>, Vec<(String, String)>);
+
 // A more sensible format for the tailoring
 #[derive(Debug, PartialEq)]
 struct Locale {
     identity: Identity,
+    default_collation: Option<String>,
     collations: Vec<Collation>,
 }
 
@@ -19,21 +41,16 @@ struct Identity {
     territory: Option<String>,
 }
 
-#[derive(Debug)]
-enum Error {
-    RuleParseError,
-    XMLError,
-}
-
-impl TryFrom<LDML> for Locale {
+impl TryFrom<Ldml> for Locale {
     type Error = Error;
-    fn try_from(ldml: LDML) -> Result<Self, Self::Error> {
+    fn try_from(ldml: Ldml) -> Result<Self, Self::Error> {
         Ok(Self {
             identity: Identity {
                 version: ldml.identity.version.number,
                 language: ldml.identity.language.r#type,
                 territory: ldml.identity.territory.map(|t| t.r#type),
             },
+            default_collation: ldml.collations.default_collation,
             collations: ldml
                 .collations
                 .collation
@@ -42,10 +59,10 @@ impl TryFrom<LDML> for Locale {
                     Ok(Collation {
                         r#type: c.r#type,
                         rules: collation_rules::cldr(&c.rules.join(""))
-                            .map_err(|_| Error::RuleParseError)?,
+                            .map_err(|_| Error::Rules)?,
                     })
                 })
-                .collect::<Result<Vec<_>, _>>()?,
+                .collect::<Result<Vec<_>, Error>>()?,
         })
     }
 }
@@ -53,7 +70,203 @@ impl TryFrom<LDML> for Locale {
 impl TryFrom<&str> for Locale {
     type Error = Error;
     fn try_from(s: &str) -> Result<Self, Self::Error> {
-        Self::try_from(LDML::from_str(s).map_err(|_| Error::XMLError)?)
+        Self::try_from(Ldml::from_str(s).map_err(|_| Error::Xml)?)
+    }
+}
+
+impl Locale {
+    /// Reads and parses the LDML file at `path`.
+    #[cfg(feature = "std")]
+    fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::try_from(fs::read_to_string(path)?.as_str())
+    }
+
+    /// Finds and parses the collation data for `tag` in `dir`, which is
+    /// either a CLDR release or its `common/collation` directory. Like CLDR,
+    /// this falls back to less specific locales, like `de_DE` to `de`.
+    /// Returns `None` if none of them has data, so the root collation
+    /// applies.
+    #[cfg(feature = "std")]
+    fn from_cldr_dir(dir: impl AsRef<Path>, tag: &LanguageTag) -> Result<Option<Self>, Error> {
+        let dir = dir.as_ref();
+        let collation_dir = dir.join("common").join("collation");
+        let dir = if collation_dir.is_dir() {
+            collation_dir.as_path()
+        } else {
+            dir
+        };
+        for name in tag.file_names() {
+            let path = dir.join(name + ".xml");
+            if path.is_file() {
+                return Self::from_path(path).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The collation of type `ty`, like `standard` or `phonebook`.
+    fn collation(&self, ty: &str) -> Option<&Collation> {
+        self.collations.iter().find(|c| c.r#type == ty)
+    }
+
+    /// The collation named by `<defaultCollation>`, or the standard
+    /// collation if there is none.
+    fn default_collation(&self) -> Option<&Collation> {
+        self.collation(self.default_collation.as_deref().unwrap_or("standard"))
+    }
+}
+
+// The parts of a BCP 47 language tag that select a collation
+#[derive(Debug, PartialEq)]
+struct LanguageTag {
+    language: String,
+    script: Option<String>,
+    region: Option<String>,
+    collation: Option<String>,
+}
+
+impl LanguageTag {
+    fn parse(id: &str) -> Option<Self> {
+        let is_alpha = |s: &str| s.chars().all(|c| c.is_ascii_alphabetic());
+        let mut subtags = id
+            .split(['-', '_'])
+            .map(|s| s.to_ascii_lowercase())
+            .peekable();
+
+        let language = subtags
+            .next()
+            .filter(|s| (2..=3).contains(&s.len()) && is_alpha(s))?;
+        let script = subtags.next_if(|s| s.len() == 4 && is_alpha(s));
+        let region = subtags.next_if(|s| {
+            s.len() == 2 && is_alpha(s) || s.len() == 3 && s.chars().all(|c| c.is_ascii_digit())
+        });
+
+        // Of the variants and extensions, only the collation keyword of the
+        // Unicode extension (`-u-co-...`) matters
+        let mut collation = None;
+        let mut in_unicode_extension = false;
+        while let Some(subtag) = subtags.next() {
+            if subtag.is_empty() || !subtag.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return None;
+            } else if subtag == "x" {
+                break;
+            } else if subtag.len() == 1 {
+                in_unicode_extension = subtag == "u";
+            } else if in_unicode_extension && subtag == "co" {
+                collation = subtags.next_if(|s| s.len() > 2);
+            }
+        }
+
+        Some(Self {
+            language,
+            script,
+            region,
+            collation,
+        })
+    }
+
+    // The names of the LDML files in CLDR that can hold the collation data,
+    // from the most to the least specific, like `zh_Hant_TW`, `zh_Hant`,
+    // `zh_TW` and `zh`
+    #[cfg(feature = "std")]
+    fn file_names(&self) -> Vec<String> {
+        let script = self.script.as_ref().map(|s| {
+            let (first, rest) = s.split_at(1);
+            format!("_{}{}", first.to_ascii_uppercase(), rest)
+        });
+        let region = self
+            .region
+            .as_ref()
+            .map(|r| format!("_{}", r.to_ascii_uppercase()));
+
+        let mut names = Vec::new();
+        for script in &[&script, &None] {
+            for region in &[&region, &None] {
+                let name = format!(
+                    "{}{}{}",
+                    self.language,
+                    script.as_deref().unwrap_or(""),
+                    region.as_deref().unwrap_or("")
+                );
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    // The collation type as named in LDML, which is not always the same as
+    // the value of the BCP 47 keyword
+    fn collation_type(&self) -> &str {
+        match self.collation.as_deref() {
+            None => "standard",
+            Some("phonebk") => "phonebook",
+            Some("trad") => "traditional",
+            Some("dict") => "dictionary",
+            Some("gb2312") => "gb2312han",
+            Some(other) => other,
+        }
+    }
+
+    // The requested collation of `locale`. Like CLDR, this falls back to the
+    // default collation of the locale and then to the root collation, which
+    // has no rules.
+    fn collation<'l>(&self, locale: &'l Locale) -> Option<&'l Collation> {
+        let collation = match self.collation {
+            Some(_) => locale.collation(self.collation_type()),
+            None => None,
+        };
+        collation.or_else(|| locale.default_collation())
+    }
+}
+
+/// Builds the root table tailored for the locale `id`, like
+/// `de-DE-u-co-phonebk`, together with the settings of the tailoring.
+pub(crate) fn tailored(id: &str) -> Result<Tailored, Error> {
+    let tag = LanguageTag::parse(id).ok_or_else(|| Error::Locale(id.to_owned()))?;
+    let (language, ldml) = match BUNDLED.iter().find(|(l, _)| *l == tag.language) {
+        Some(&bundled) => bundled,
+        None => return Ok((Cow::Borrowed(root_table()), Vec::new())),
+    };
+
+    // Holding the lock while building, so no table is built twice
+    let mut tables = TABLES.lock().unwrap();
+    if let Some((table, settings)) = tables.get(&(language, tag.collation_type().to_owned())) {
+        return Ok((Cow::Borrowed(*table), settings.clone()));
+    }
+    let locale = Locale::try_from(ldml)?;
+    let collation = match tag.collation(&locale) {
+        Some(collation) => collation,
+        None => return Ok((Cow::Borrowed(root_table()), Vec::new())),
+    };
+    // A collation type that the locale doesn't have falls back to another
+    // one, which is stored under its own type
+    let key = (language, collation.r#type.clone());
+    if !tables.contains_key(&key) {
+        let mut table = root_table().clone();
+        table.apply_rules(&collation.rules)?;
+        let table = &*Box::leak(Box::new(table));
+        tables.insert(key.clone(), (table, collation.rules.settings.clone()));
+    }
+    let (table, settings) = &tables[&key];
+    Ok((Cow::Borrowed(*table), settings.clone()))
+}
+
+/// Builds the root table tailored for the locale `id` with the collation
+/// data in the CLDR directory `dir`, together with the settings of the
+/// tailoring.
+#[cfg(feature = "std")]
+pub(crate) fn tailored_from_cldr_dir(dir: &Path, id: &str) -> Result<Tailored, Error> {
+    let tag = LanguageTag::parse(id).ok_or_else(|| Error::Locale(id.to_owned()))?;
+    let locale = Locale::from_cldr_dir(dir, &tag)?;
+    match locale.as_ref().and_then(|l| tag.collation(l)) {
+        Some(collation) => {
+            let mut table = root_table().clone();
+            table.apply_rules(&collation.rules)?;
+            Ok((Cow::Owned(table), collation.rules.settings.clone()))
+        }
+        None => Ok((Cow::Borrowed(root_table()), Vec::new())),
     }
 }
 
@@ -85,6 +298,7 @@ mod tests {
                     language: "af".into(),
                     territory: None,
                 },
+                default_collation: None,
                 collations: vec![Collation {
                     r#type: "standard".into(),
                     rules: CollationRules {
@@ -107,4 +321,175 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn default_collation() {
+        let ldml = |default: &str| {
+            format!(
+                "<ldml>
+                    <identity>
+                        <version number=\"$Revision$\"/>
+                        <language type=\"es\"/>
+                    </identity>
+                    <collations>
+                        {}
+                        <collation type=\"standard\">
+                            <cr><![CDATA[&N<ñ]]></cr>
+                        </collation>
+                        <collation type=\"traditional\">
+                            <cr><![CDATA[&C<ch]]></cr>
+                        </collation>
+                    </collations>
+                </ldml>",
+                default
+            )
+        };
+
+        let locale =
+            Locale::try_from(ldml("<defaultCollation>traditional</defaultCollation>").as_str())
+                .unwrap();
+        assert_eq!(locale.default_collation.as_deref(), Some("traditional"));
+        assert_eq!(locale.default_collation().unwrap().r#type, "traditional");
+        assert_eq!(locale.collation("standard").unwrap().r#type, "standard");
+        assert!(locale.collation("phonebook").is_none());
+
+        // Without a default, the standard collation is used
+        let locale = Locale::try_from(ldml("").as_str()).unwrap();
+        assert_eq!(locale.default_collation().unwrap().r#type, "standard");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn from_path() {
+        // Unique to the process, so that parallel test runs don't collide
+        let path = std::env::temp_dir().join(format!(
+            "collate_locale_from_path_{}.xml",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "<ldml>
+                <identity>
+                    <version number=\"$Revision$\"/>
+                    <language type=\"af\"/>
+                </identity>
+                <collations>
+                    <collation type=\"standard\">
+                        <cr><![CDATA[&N<<<ŉ]]></cr>
+                    </collation>
+                </collations>
+            </ldml>",
+        )
+        .unwrap();
+        let locale = Locale::from_path(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(locale.identity.language, "af");
+        assert_eq!(locale.collations.len(), 1);
+        assert!(matches!(Locale::from_path(&path), Err(Error::Io(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn from_cldr_dir() {
+        use std::path::Path;
+
+        let dir = std::env::temp_dir().join(format!(
+            "collate_locale_from_cldr_dir_{}",
+            std::process::id()
+        ));
+        let collation_dir = dir.join("common").join("collation");
+        std::fs::create_dir_all(&collation_dir).unwrap();
+        std::fs::write(
+            collation_dir.join("af.xml"),
+            "<ldml>
+                <identity>
+                    <version number=\"$Revision$\"/>
+                    <language type=\"af\"/>
+                </identity>
+                <collations>
+                    <collation type=\"standard\">
+                        <cr><![CDATA[&N<<<ŉ]]></cr>
+                    </collation>
+                </collations>
+            </ldml>",
+        )
+        .unwrap();
+        let load = |dir: &Path, id| Locale::from_cldr_dir(dir, &LanguageTag::parse(id).unwrap());
+
+        let locale = load(&dir, "af").unwrap().unwrap();
+        assert_eq!(locale.identity.language, "af");
+        assert_eq!(locale.collations.len(), 1);
+        assert_eq!(load(&collation_dir, "af").unwrap(), Some(locale));
+        // Falling back to the language, and then to the root
+        assert!(load(&dir, "af-ZA").unwrap().is_some());
+        assert!(load(&dir, "af-Latn-NA-u-co-phonebk").unwrap().is_some());
+        assert!(load(&dir, "nl").unwrap().is_none());
+
+        let (table, settings) = tailored_from_cldr_dir(&dir, "af-ZA").unwrap();
+        assert!(settings.is_empty());
+        // Tailored from an expansion to a single element after `N`
+        assert_eq!(table["ŉ"].len(), 1);
+        assert_eq!(table["ŉ"][0].primary, table["N"][0].primary);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn cldr_file_names() {
+        let names = |id| LanguageTag::parse(id).unwrap().file_names();
+        assert_eq!(names("de"), ["de"]);
+        assert_eq!(names("de-de"), ["de_DE", "de"]);
+        assert_eq!(
+            names("zh-hant-tw"),
+            ["zh_Hant_TW", "zh_Hant", "zh_TW", "zh"]
+        );
+    }
+
+    #[test]
+    fn language_tags() {
+        assert_eq!(
+            LanguageTag::parse("de-DE-u-co-phonebk"),
+            Some(LanguageTag {
+                language: "de".into(),
+                script: None,
+                region: Some("de".into()),
+                collation: Some("phonebk".into()),
+            })
+        );
+        let tag = LanguageTag::parse("zh_Hant_TW").unwrap();
+        assert_eq!(tag.script.as_deref(), Some("hant"));
+        assert_eq!(tag.region.as_deref(), Some("tw"));
+        assert_eq!(tag.collation_type(), "standard");
+
+        let tag = LanguageTag::parse("es-419-u-ca-gregory-co-trad-x-co-phonebk").unwrap();
+        assert_eq!(tag.region.as_deref(), Some("419"));
+        assert_eq!(tag.collation_type(), "traditional");
+
+        assert_eq!(LanguageTag::parse(""), None);
+        assert_eq!(LanguageTag::parse("german"), None);
+        assert_eq!(LanguageTag::parse("de--DE"), None);
+    }
+
+    #[test]
+    fn cached_tables() {
+        for id in ["fr", "de-DE", "es-u-co-xyzzy", "es-u-co-trad"] {
+            tailored(id).unwrap();
+        }
+        // Only the tables of bundled collations are kept, once each
+        let tables = TABLES.lock().unwrap();
+        assert!(tables
+            .keys()
+            .all(|(language, ty)| BUNDLED.iter().any(|(l, _)| l == language) && ty != "xyzzy"));
+        assert!(tables.contains_key(&("es", "traditional".to_owned())));
+    }
+
+    #[test]
+    fn bundled_locales() {
+        for (language, ldml) in BUNDLED {
+            let locale = Locale::try_from(*ldml).unwrap();
+            assert_eq!(locale.identity.language, *language);
+        }
+    }
 }
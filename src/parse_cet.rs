@@ -1,44 +1,150 @@
-use crate::CollationElement;
+use crate::{CollationElement, CollationElementTable, ImplicitRange, ParseWarning};
 use nom::{
     branch::alt,
     bytes::complete::{is_not, tag},
-    character::complete::{char, hex_digit1, line_ending, not_line_ending, space0},
-    combinator::{all_consuming, map, map_opt, map_res, opt, value},
-    multi::{many1, separated_list1},
-    sequence::{delimited, separated_pair, terminated, tuple},
+    character::complete::{char, digit1, hex_digit1, line_ending, not_line_ending, space0, space1},
+    combinator::{all_consuming, consumed, map, map_res, opt, value},
+    multi::{fold_many1, many1, separated_list1},
+    sequence::{delimited, pair, separated_pair, terminated, tuple},
     IResult,
 };
-use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
 
+#[derive(Clone)]
+enum Line {
+    Empty,
+    Version(String, (u8, u8)),
+    ImplicitWeights(RangeInclusive<u32>, u16),
+    Row(String, Vec<CollationElement>),
+    // A row with a code point that isn't a `char`
+    Skipped,
+}
+
+// Parses all lines, but only adds the rows for which `keep` is true. The rows
+// that are skipped because of their code points are returned as warnings.
 pub fn table<'a>(
     i: &'a str,
-    data: &mut BTreeMap<String, Vec<CollationElement>>,
-) -> IResult<&'a str, ()> {
-    value(
-        (),
-        all_consuming(many1(alt((
-            // Empty line
-            value((), tag("\n")),
-            // A comment
-            value(
-                (),
-                tuple((space0, char('#'), opt(is_not("\n")), char('\n'))),
-            ),
-            // TODO: Implicit weight and version
-            value((), tuple((tag("@version"), is_not("\n"), char('\n')))),
-            value(
-                (),
-                tuple((tag("@implicitweights"), is_not("\n"), char('\n'))),
-            ),
-            // A row in the table
-            map(row, |(char_points, key)| {
-                data.insert(char_points, key);
+    table: &mut CollationElementTable,
+    keep: impl Fn(&str, &[CollationElement]) -> bool,
+) -> IResult<&'a str, Vec<ParseWarning>> {
+    all_consuming(fold_many1(
+        consumed(line),
+        Vec::new(),
+        |mut warnings, (content, line)| {
+            match line {
+                Line::Skipped => {
+                    let offset = content.as_ptr() as usize - i.as_ptr() as usize;
+                    warnings.push(ParseWarning {
+                        line: i[..offset].matches('\n').count() + 1,
+                        // The row itself may end before a comment
+                        content: i[offset..].lines().next().unwrap_or("").to_owned(),
+                    });
+                }
+                Line::Row(ref char_points, ref key) if !keep(char_points, key) => {}
+                line => add(table, line),
+            }
+            warnings
+        },
+    ))(i)
+}
+
+// Parses the lines one by one, skipping the ones that can't be parsed
+pub fn table_lenient(i: &str, table: &mut CollationElementTable) -> Vec<ParseWarning> {
+    let mut warnings = Vec::new();
+    for (n, content) in i.split_inclusive('\n').enumerate() {
+        // The last line may lack a line ending
+        let terminated;
+        let content = if content.ends_with('\n') {
+            content
+        } else {
+            terminated = format!("{}\n", content);
+            &terminated
+        };
+        match all_consuming(many1(line))(content) {
+            Ok((_, lines)) => {
+                for line in lines {
+                    if let Line::Skipped = line {
+                        warnings.push(ParseWarning {
+                            line: n + 1,
+                            content: content.trim_end().to_owned(),
+                        });
+                    }
+                    add(table, line);
+                }
+            }
+            Err(_) => warnings.push(ParseWarning {
+                line: n + 1,
+                content: content.trim_end().to_owned(),
             }),
-        )))),
+        };
+    }
+    warnings
+}
+
+fn add(table: &mut CollationElementTable, line: Line) {
+    match line {
+        Line::Empty | Line::Skipped => {}
+        Line::Version(version, unicode_version) => {
+            table.header.version = Some(version);
+            table.header.unicode_version = Some(unicode_version);
+        }
+        Line::ImplicitWeights(range, base) => table
+            .header
+            .implicit_weights
+            .push(ImplicitRange { range, base }),
+        Line::Row(char_points, key) => {
+            table.data.insert(char_points.into(), key.into());
+        }
+    }
+}
+
+fn line(i: &str) -> IResult<&str, Line> {
+    alt((
+        // Empty line
+        value(Line::Empty, tag("\n")),
+        // A comment
+        value(
+            Line::Empty,
+            tuple((space0, char('#'), opt(is_not("\n")), char('\n'))),
+        ),
+        version,
+        implicit_weights,
+        // A row in the table
+        map(row, |(char_points, key)| match char_points {
+            Some(char_points) => Line::Row(char_points, key),
+            None => Line::Skipped,
+        }),
+    ))(i)
+}
+
+// The whole version, and the major and minor version, which are the ones
+// that matter for the assigned characters
+fn version(i: &str) -> IResult<&str, Line> {
+    map(
+        delimited(
+            pair(tag("@version"), space1),
+            consumed(terminated(
+                separated_pair(number, char('.'), number),
+                opt(is_not(" \t#\n")),
+            )),
+            pair(opt(is_not("\n")), char('\n')),
+        ),
+        |(version, unicode_version): (&str, _)| Line::Version(version.to_owned(), unicode_version),
     )(i)
 }
 
-fn row(i: &str) -> IResult<&str, (String, Vec<CollationElement>)> {
+fn implicit_weights(i: &str) -> IResult<&str, Line> {
+    map(
+        delimited(
+            pair(tag("@implicitweights"), space1),
+            separated_pair(separated_pair(hex_u32, tag(".."), hex_u32), sep, hex),
+            pair(opt(is_not("\n")), char('\n')),
+        ),
+        |((start, end), base)| Line::ImplicitWeights(start..=end, base),
+    )(i)
+}
+
+fn row(i: &str) -> IResult<&str, (Option<String>, Vec<CollationElement>)> {
     terminated(separated_pair(element, sep, many1(sortkey)), opt(comment))(i)
 }
 
@@ -46,17 +152,13 @@ fn comment(i: &str) -> IResult<&str, ()> {
     value((), tuple((char('#'), not_line_ending, line_ending)))(i)
 }
 
-fn element(i: &str) -> IResult<&str, String> {
-    map(separated_list1(char(' '), code_point), |v| {
-        v.into_iter().collect::<String>()
-    })(i)
-}
-
-fn code_point(i: &str) -> IResult<&str, char> {
-    map_opt(hex_digit1, |out: &str| {
-        u32::from_str_radix(out, 16)
-            .ok()
-            .and_then(|u| char::from_u32(u))
+// The characters of a row, or `None` if it contains a code point that isn't a
+// `char`, like a surrogate. Such rows can never match a string, so they are
+// skipped with a warning instead of failing the whole table. Noncharacters
+// like U+FFFF are valid `char`s and are kept with the weights of the table.
+fn element(i: &str) -> IResult<&str, Option<String>> {
+    map(separated_list1(char(' '), hex_u32), |v| {
+        v.into_iter().map(char::from_u32).collect()
     })(i)
 }
 
@@ -75,13 +177,18 @@ fn sortkey(i: &str) -> IResult<&str, CollationElement> {
             i,
             CollationElement {
                 variable: var,
+                // Tertiary weights of DUCET fit in a byte
+                tertiary_class: levels[2].min(0xFF) as u8,
                 primary: levels[0],
                 secondary: levels[1],
                 tertiary: levels[2],
             },
         ))
     } else {
-        Err(nom::Err::Incomplete(nom::Needed::Unknown))
+        Err(nom::Err::Error(nom::error::Error::new(
+            i,
+            nom::error::ErrorKind::Verify,
+        )))
     }
 }
 
@@ -89,6 +196,14 @@ fn variable(i: &str) -> IResult<&str, bool> {
     alt((value(true, char('*')), value(false, char('.'))))(i)
 }
 
+fn number(i: &str) -> IResult<&str, u8> {
+    map_res(digit1, |out: &str| out.parse())(i)
+}
+
+fn hex_u32(i: &str) -> IResult<&str, u32> {
+    map_res(hex_digit1, |out: &str| u32::from_str_radix(out, 16))(i)
+}
+
 fn hex(i: &str) -> IResult<&str, u16> {
     map_res(hex_digit1, |out: &str| u16::from_str_radix(out, 16))(i)
 }
@@ -0,0 +1,15 @@
+//! The types needed for most uses of the crate.
+//!
+//! ```
+//! use collate::prelude::*;
+//!
+//! let collator = Collator::default();
+//! let mut words = ["banana", "Apple", "apple", "cherry"];
+//! words.sort_by(|a, b| collator.compare(a, b));
+//! assert_eq!(words, ["apple", "Apple", "banana", "cherry"]);
+//! ```
+
+pub use crate::{
+    CollationElementTable, Collator, Error, Level, MaxVariable, SortKey, Strength, TailoringError,
+    VariableWeighting,
+};
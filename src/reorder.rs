@@ -0,0 +1,225 @@
+use crate::{CollationElementTable, TailoringError};
+use std::ops::RangeInclusive;
+use unic_ucd_category::GeneralCategory;
+
+// The scripts that can be reordered, by their ISO 15924 codes, with the
+// blocks of their letters. Scripts that share primary weights, like Hiragana
+// and Katakana, are one group.
+const SCRIPTS: &[(&[&str], &[RangeInclusive<char>])] = &[
+    (
+        &["Latn"],
+        &[
+            '\u{41}'..='\u{24f}',
+            '\u{1e00}'..='\u{1eff}',
+            '\u{2c60}'..='\u{2c7f}',
+            '\u{a720}'..='\u{a7ff}',
+            '\u{ab30}'..='\u{ab6f}',
+        ],
+    ),
+    (&["Grek"], &['\u{370}'..='\u{3ff}', '\u{1f00}'..='\u{1fff}']),
+    (&["Copt"], &['\u{2c80}'..='\u{2cff}']),
+    (
+        &["Cyrl"],
+        &[
+            '\u{400}'..='\u{52f}',
+            '\u{1c80}'..='\u{1c8f}',
+            '\u{2de0}'..='\u{2dff}',
+            '\u{a640}'..='\u{a69f}',
+        ],
+    ),
+    (&["Glag"], &['\u{2c00}'..='\u{2c5f}']),
+    (
+        &["Geor"],
+        &[
+            '\u{10a0}'..='\u{10ff}',
+            '\u{1c90}'..='\u{1cbf}',
+            '\u{2d00}'..='\u{2d2f}',
+        ],
+    ),
+    (&["Armn"], &['\u{530}'..='\u{58f}']),
+    (&["Hebr"], &['\u{590}'..='\u{5ff}']),
+    (
+        &["Arab"],
+        &[
+            '\u{600}'..='\u{6ff}',
+            '\u{750}'..='\u{77f}',
+            '\u{8a0}'..='\u{8ff}',
+        ],
+    ),
+    (&["Syrc"], &['\u{700}'..='\u{74f}']),
+    (&["Thaa"], &['\u{780}'..='\u{7bf}']),
+    (&["Deva"], &['\u{900}'..='\u{97f}']),
+    (&["Beng"], &['\u{980}'..='\u{9ff}']),
+    (&["Guru"], &['\u{a00}'..='\u{a7f}']),
+    (&["Gujr"], &['\u{a80}'..='\u{aff}']),
+    (&["Orya"], &['\u{b00}'..='\u{b7f}']),
+    (&["Taml"], &['\u{b80}'..='\u{bff}']),
+    (&["Telu"], &['\u{c00}'..='\u{c7f}']),
+    (&["Knda"], &['\u{c80}'..='\u{cff}']),
+    (&["Mlym"], &['\u{d00}'..='\u{d7f}']),
+    (&["Sinh"], &['\u{d80}'..='\u{dff}']),
+    (&["Thai"], &['\u{e00}'..='\u{e7f}']),
+    (&["Laoo"], &['\u{e80}'..='\u{eff}']),
+    (&["Tibt"], &['\u{f00}'..='\u{fff}']),
+    (&["Mymr"], &['\u{1000}'..='\u{109f}']),
+    (&["Ethi"], &['\u{1200}'..='\u{139f}']),
+    (&["Cher"], &['\u{13a0}'..='\u{13ff}']),
+    (&["Khmr"], &['\u{1780}'..='\u{17ff}']),
+    (&["Mong"], &['\u{1800}'..='\u{18af}']),
+    (
+        &["Hang"],
+        &[
+            '\u{1100}'..='\u{11ff}',
+            '\u{3130}'..='\u{318f}',
+            '\u{a960}'..='\u{a97f}',
+            '\u{d7b0}'..='\u{d7ff}',
+        ],
+    ),
+    (
+        &["Hrkt", "Hira", "Kana"],
+        &['\u{3040}'..='\u{30ff}', '\u{31f0}'..='\u{31ff}'],
+    ),
+    (
+        &["Bopo"],
+        &['\u{3100}'..='\u{312f}', '\u{31a0}'..='\u{31bf}'],
+    ),
+];
+
+// The primary weights from here on are implicit or trailing, which are not
+// reordered
+const FIRST_IMPLICIT_PRIMARY: u16 = 0xFB00;
+
+/// A permutation of the primary weights of scripts, like `[reorder Grek
+/// others]` to sort Greek before all other scripts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Reordering {
+    // The first primary weight of each range of weights that is moved, in
+    // order, with the amount that it is moved by
+    ranges: Vec<(u16, i32)>,
+}
+
+impl Reordering {
+    // Each script is the range of primary weights from its lowest letter up
+    // to the next script, so characters of scripts that are not known here
+    // move with the script before them. Scripts that are not listed take the
+    // place of `others` (or `Zzzz`), which is at the end if it isn't listed.
+    pub(crate) fn new(
+        table: &CollationElementTable,
+        codes: &[&str],
+    ) -> Result<Self, TailoringError> {
+        let mut starts = vec![u16::MAX; SCRIPTS.len()];
+        for (key, elements) in table.iter() {
+            let mut chars = key.chars();
+            let (c, primary) = match (chars.next(), chars.next(), elements.first()) {
+                (Some(c), None, Some(elem)) if !elem.variable => (c, elem.primary),
+                _ => continue,
+            };
+            // Modifier letters, like iteration marks, can sort with the
+            // symbols instead
+            let gc = GeneralCategory::of(c);
+            if primary == 0
+                || primary >= FIRST_IMPLICIT_PRIMARY
+                || !gc.is_letter()
+                || gc == GeneralCategory::ModifierLetter
+            {
+                continue;
+            }
+            if let Some(i) = SCRIPTS
+                .iter()
+                .position(|(_, blocks)| blocks.iter().any(|b| b.contains(&c)))
+            {
+                starts[i] = starts[i].min(primary);
+            }
+        }
+
+        // The scripts of the table in their current order
+        let mut scripts: Vec<_> = (0..SCRIPTS.len())
+            .filter(|&i| starts[i] != u16::MAX)
+            .collect();
+        scripts.sort_by_key(|&i| starts[i]);
+        let end = |i: usize| {
+            let next = scripts.iter().map(|&j| starts[j]).find(|&s| s > starts[i]);
+            next.unwrap_or(FIRST_IMPLICIT_PRIMARY)
+        };
+
+        let mut order = Vec::new();
+        let mut others = None;
+        for code in codes {
+            if code.eq_ignore_ascii_case("others") || code.eq_ignore_ascii_case("Zzzz") {
+                others = Some(order.len());
+                continue;
+            }
+            let i = SCRIPTS
+                .iter()
+                .position(|(names, _)| names.iter().any(|n| n.eq_ignore_ascii_case(code)))
+                .ok_or_else(|| TailoringError::UnknownScript(code.to_string()))?;
+            // Scripts without letters in the table don't move anything
+            if starts[i] != u16::MAX && !order.contains(&i) {
+                order.push(i);
+            }
+        }
+        let unlisted = scripts.iter().copied().filter(|i| !order.contains(i));
+        order.splice(
+            others.unwrap_or(order.len())..others.unwrap_or(order.len()),
+            unlisted.collect::<Vec<_>>(),
+        );
+
+        let mut next = scripts.first().map_or(0, |&i| starts[i]);
+        let mut ranges: Vec<_> = order
+            .iter()
+            .map(|&i| {
+                let offset = next as i32 - starts[i] as i32;
+                next += end(i) - starts[i];
+                (starts[i], offset)
+            })
+            .collect();
+        ranges.sort_unstable();
+        Ok(Self { ranges })
+    }
+
+    // The reordered weight of `primary`
+    pub(crate) fn apply(&self, primary: u16) -> u16 {
+        if primary >= FIRST_IMPLICIT_PRIMARY {
+            return primary;
+        }
+        match self.ranges.partition_point(|&(start, _)| start <= primary) {
+            0 => primary,
+            i => (primary as i32 + self.ranges[i - 1].1) as u16,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "bundled-ducet"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permutation() {
+        let table = CollationElementTable::default();
+        let primary = |c: &str| table[c][0].primary;
+        let reordering = Reordering::new(&table, &["Cyrl", "Grek", "others"]).unwrap();
+        let reordered = |c| reordering.apply(primary(c));
+
+        assert!(reordered("\u{430}") < reordered("\u{3b1}"));
+        assert!(reordered("\u{3c9}") < reordered("a"));
+        assert!(reordered("a") < reordered("z"));
+        assert!(reordered("z") < reordered("\u{5d0}"));
+        // Digits and implicit weights stay where they are
+        assert_eq!(reordered("1"), primary("1"));
+        assert!(reordered("1") < reordered("\u{430}"));
+        assert_eq!(reordering.apply(0xFB40), 0xFB40);
+
+        // Every weight is still used once
+        let mut weights: Vec<_> = (0..FIRST_IMPLICIT_PRIMARY)
+            .map(|p| reordering.apply(p))
+            .collect();
+        weights.sort_unstable();
+        weights.dedup();
+        assert_eq!(weights.len(), FIRST_IMPLICIT_PRIMARY as usize);
+
+        assert_eq!(
+            Reordering::new(&table, &["Latn", "Xyzw"]),
+            Err(TailoringError::UnknownScript("Xyzw".into()))
+        );
+    }
+}
@@ -0,0 +1,922 @@
+use crate::{
+    collation_rules::{CollationRules, Position, Rule, SequenceElement},
+    starts_with_ascii_pair, CollationElement, CollationElementTable, CollationElements, Level,
+    TailoringError,
+};
+use std::collections::BTreeSet;
+use unic_normal::StrNormalForm;
+
+// Primary weights from here on are implicit or special weights, which are
+// never moved to make room for tailored weights
+const FIRST_IMPLICIT_PRIMARY: u16 = 0xFB00;
+// Primary weights from here on are trailing weights, like that of U+FFFD
+const FIRST_TRAILING_PRIMARY: u16 = 0xFC00;
+const COMMON_SECONDARY: u16 = 0x0020;
+const COMMON_TERTIARY: u16 = 0x0002;
+// Tertiary weights stay within a byte, which leaves the bits above them for
+// the case when it sorts first (see `Collator::set_case_first`)
+const MAX_TERTIARY: u16 = 0x00FF;
+
+impl CollationElementTable {
+    /// Applies parsed CLDR tailoring rules to the table.
+    ///
+    /// Every relation places its sequence directly after the previous one at
+    /// the level of the relation, starting from the last reset. Weights that
+    /// are in the way are moved up, which keeps the order of all existing
+    /// entries intact. Rules with a prefix (`|`) only apply when the sequence
+    /// directly follows the prefix.
+    pub fn apply_rules(&mut self, rules: &CollationRules) -> Result<(), TailoringError> {
+        let left = relations_left(&rules.rules);
+        let mut weights = self.weight_index();
+        let mut anchor = Vec::new();
+        for (rule, left) in rules.rules.iter().zip(left) {
+            match rule {
+                Rule::SetContext { before, sequence } => {
+                    anchor = self
+                        .elements_of(sequence)
+                        .ok_or_else(|| TailoringError::AnchorNotFound(sequence.clone()))?;
+                    step_back(&mut anchor, *before)?;
+                }
+                Rule::SetPosition { before, position } => {
+                    anchor = self.position_elements(*position).ok_or_else(|| {
+                        TailoringError::AnchorNotFound(format!("[{}]", position.name()))
+                    })?;
+                    step_back(&mut anchor, *before)?;
+                }
+                Rule::Equal {
+                    prefix,
+                    extension,
+                    sequence,
+                } => self.insert_relation(prefix, sequence, extension, anchor.clone())?,
+                Rule::MultiEqual { multisequence } => {
+                    for c in chars(multisequence) {
+                        self.insert(c.encode_utf8(&mut [0; 4]), anchor.clone());
+                    }
+                }
+                Rule::Increment {
+                    level,
+                    prefix,
+                    extension,
+                    sequence,
+                } => {
+                    let left = left_at(&left, *level);
+                    anchor = self.increment(&mut weights, &anchor, *level, left)?;
+                    let elements = self.with_classes_of(sequence, anchor.clone());
+                    self.insert_relation(prefix, sequence, extension, elements)?;
+                }
+                Rule::MultiIncrement {
+                    level,
+                    multisequence,
+                } => {
+                    let left = left_at(&left, *level);
+                    for (i, c) in chars(multisequence).enumerate() {
+                        let left = left.saturating_sub(i as u16).max(1);
+                        anchor = self.increment(&mut weights, &anchor, *level, left)?;
+                        let mut buf = [0; 4];
+                        let c = c.encode_utf8(&mut buf);
+                        let elements = self.with_classes_of(c, anchor.clone());
+                        self.insert(c, elements);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Gives `sequence` the elements of the relation, followed by those of the
+    // extension
+    fn insert_relation(
+        &mut self,
+        prefix: &Option<String>,
+        sequence: &str,
+        extension: &Option<String>,
+        mut elements: Vec<CollationElement>,
+    ) -> Result<(), TailoringError> {
+        if let Some(extension) = extension {
+            elements.extend(
+                self.elements_of(extension)
+                    .ok_or_else(|| TailoringError::ExpansionTargetMissing(extension.clone()))?,
+            );
+        }
+        match prefix {
+            Some(prefix) => {
+                self.insert_prefixed(sequence.nfd().collect(), prefix.nfd().collect(), elements)
+            }
+            None => self.insert(sequence, elements),
+        }
+        Ok(())
+    }
+
+    // Gives the elements of an increment the tertiary classes of the elements
+    // that `sequence` has itself, so that it keeps its case and kana type
+    // wherever it is placed. Elements past the end of those of `sequence`
+    // take the class of its last element. Equal sequences keep the classes
+    // of the anchor instead, like the rest of its elements.
+    fn with_classes_of(
+        &self,
+        sequence: &str,
+        mut elements: Vec<CollationElement>,
+    ) -> Vec<CollationElement> {
+        let classes: Vec<u8> = CollationElements::from_str(self, sequence)
+            .map(|elem| elem.tertiary_class)
+            .collect();
+        if let Some(&last) = classes.last() {
+            for (i, elem) in elements.iter_mut().enumerate() {
+                elem.tertiary_class = *classes.get(i).unwrap_or(&last);
+            }
+        }
+        elements
+    }
+
+    // The elements of a sequence, if all its characters are in the table or
+    // get meaningful implicit weights, like ideographs
+    fn elements_of(&self, sequence: &str) -> Option<Vec<CollationElement>> {
+        let mut buf = [0; 4];
+        if sequence.is_empty()
+            || sequence.nfd().any(|c| {
+                !self.data.contains_key(&*c.encode_utf8(&mut buf)) && !self.has_implicit_weights(c)
+            })
+        {
+            return None;
+        }
+        Some(CollationElements::from_str(self, sequence).collect())
+    }
+
+    // The element at a logical position, which is the lowest or highest
+    // element of its kind in the table
+    fn position_elements(&self, position: Position) -> Option<Vec<CollationElement>> {
+        let secondary_ignorable =
+            |e: &CollationElement| e.primary == 0 && e.secondary == 0 && e.tertiary != 0;
+        let primary_ignorable = |e: &CollationElement| e.primary == 0 && e.secondary != 0;
+        let variable = |e: &CollationElement| e.variable && e.primary != 0;
+        let regular = |e: &CollationElement| {
+            !e.variable && e.primary != 0 && e.primary < FIRST_IMPLICIT_PRIMARY
+        };
+        let trailing = |e: &CollationElement| e.primary >= FIRST_TRAILING_PRIMARY;
+
+        let (matches, first): (&dyn Fn(&CollationElement) -> bool, bool) = match position {
+            Position::FirstTertiaryIgnorable | Position::LastTertiaryIgnorable => {
+                return Some(vec![CollationElement {
+                    variable: false,
+                    tertiary_class: 0,
+                    primary: 0,
+                    secondary: 0,
+                    tertiary: 0,
+                }])
+            }
+            Position::FirstImplicit => return Some(self.implicit_elements('\u{4e00}')),
+            Position::FirstSecondaryIgnorable => (&secondary_ignorable, true),
+            Position::LastSecondaryIgnorable => (&secondary_ignorable, false),
+            Position::FirstPrimaryIgnorable => (&primary_ignorable, true),
+            Position::LastPrimaryIgnorable => (&primary_ignorable, false),
+            Position::FirstVariable => (&variable, true),
+            Position::LastVariable => (&variable, false),
+            Position::FirstRegular => (&regular, true),
+            Position::LastRegular => (&regular, false),
+            Position::FirstTrailing => (&trailing, true),
+            Position::LastTrailing => (&trailing, false),
+        };
+
+        // The second elements of implicit weights are never at a position.
+        // U+FFFD and U+FFFF always have trailing weights.
+        let candidates = self
+            .data
+            .values()
+            .flat_map(|elements| {
+                (0..elements.len())
+                    .filter(move |&i| {
+                        i == 0
+                            || !(FIRST_IMPLICIT_PRIMARY..=0xFBFF).contains(&elements[i - 1].primary)
+                    })
+                    .map(move |i| elements[i].clone())
+            })
+            .chain(self.implicit_elements('\u{fffd}'))
+            .chain(self.implicit_elements('\u{ffff}'))
+            .filter(|e| matches(e));
+        let elem = if first {
+            candidates.min()
+        } else {
+            candidates.max()
+        };
+        Some(vec![elem?])
+    }
+
+    fn insert(&mut self, sequence: &str, elements: Vec<CollationElement>) {
+        // Keys are looked up after normalization
+        let key: String = sequence.nfd().collect();
+        self.max_contraction_len = self.max_contraction_len.max(key.chars().count());
+        self.ascii_contractions |= starts_with_ascii_pair(&key);
+        self.data.insert(key.as_str().into(), elements.into());
+        self.index_gaps(&key);
+    }
+
+    // The elements of a sequence sorting directly after `anchor` at `level`.
+    // Quaternary relations can't be expressed in the table, so they are
+    // treated as equal.
+    //
+    // `left` is the number of relations at `level` up to the next reset,
+    // including this one. The free weights after `anchor` are divided evenly
+    // between them, and if there are too few, the weights in the way are
+    // moved up far enough for all of them at once, instead of once for every
+    // relation.
+    fn increment(
+        &mut self,
+        weights: &mut WeightIndex,
+        anchor: &[CollationElement],
+        level: u8,
+        left: u16,
+    ) -> Result<Vec<CollationElement>, TailoringError> {
+        let mut elements = anchor.to_vec();
+        // The implicit weights of ideographs can't be moved, so a primary
+        // relation after them continues the anchor with an element after the
+        // regular ones instead. That sorts before the next ideograph, but
+        // after the anchor followed by anything that has a regular weight.
+        let n = elements.len();
+        if level == 1
+            && n > 1
+            && (FIRST_IMPLICIT_PRIMARY..FIRST_TRAILING_PRIMARY).contains(&elements[n - 2].primary)
+        {
+            elements.extend(
+                self.position_elements(Position::LastRegular)
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+        let current = match elements.last() {
+            Some(last) if level <= 3 => weight(last, level),
+            _ => return Ok(elements),
+        };
+
+        let new_weight = match weight_between(current, weights.next(level, current), left) {
+            Some(w) => w,
+            None => {
+                if !self.has_room_above(level, current, left) {
+                    return Err(TailoringError::WeightOverflow {
+                        level: match level {
+                            1 => Level::Primary,
+                            2 => Level::Secondary,
+                            _ => Level::Tertiary,
+                        },
+                    });
+                }
+                let prefixed = self.prefixed.values_mut().flatten();
+                for entry in self
+                    .data
+                    .values_mut()
+                    .map(|elements| &mut elements[..])
+                    .chain(prefixed.map(|(_, elements)| &mut elements[..]))
+                {
+                    make_room(entry, level, current, left);
+                }
+                make_room(&mut elements, level, current, left);
+                weights.make_room(level, current, left);
+                if level == 3 {
+                    if self.moved_tertiaries.is_empty() {
+                        self.moved_tertiaries = (0..=MAX_TERTIARY).collect();
+                    }
+                    for w in &mut self.moved_tertiaries {
+                        if *w > current {
+                            *w = w.saturating_add(left);
+                        }
+                    }
+                }
+                current + 1
+            }
+        };
+        let last = elements.last_mut().unwrap();
+        *weight_mut(last, level) = new_weight;
+        if level < 2 {
+            last.secondary = COMMON_SECONDARY;
+        }
+        if level < 3 {
+            last.tertiary = COMMON_TERTIARY;
+        }
+        weights.add(&elements);
+        Ok(elements)
+    }
+
+    // Whether the weights above `above` at `level` can all be moved up by
+    // `by` without leaving the range of their kind of weight. Primary weights
+    // below the implicit weights have to stay below them, and tertiary
+    // weights within a byte.
+    fn has_room_above(&self, level: u8, above: u16, by: u16) -> bool {
+        let limit = match level {
+            1 if above < FIRST_IMPLICIT_PRIMARY => FIRST_IMPLICIT_PRIMARY - 1,
+            3 => MAX_TERTIARY,
+            _ => u16::MAX,
+        };
+        let limit = match limit.checked_sub(by) {
+            Some(limit) => limit,
+            None => return false,
+        };
+        let prefixed = self.prefixed.values().flatten();
+        above <= limit
+            && self
+                .data
+                .values()
+                .map(|elements| &elements[..])
+                .chain(prefixed.map(|(_, elements)| &elements[..]))
+                .all(|elements| {
+                    (0..elements.len()).all(|i| {
+                        (level == 1 && is_implicit(elements, i))
+                            || weight(&elements[i], level) <= limit
+                    })
+                })
+    }
+
+    // The index of the weights in the table
+    fn weight_index(&self) -> WeightIndex {
+        let mut weights = WeightIndex::default();
+        let prefixed = self.prefixed.values().flatten();
+        for elements in self
+            .data
+            .values()
+            .map(|elements| &elements[..])
+            .chain(prefixed.map(|(_, elements)| &elements[..]))
+        {
+            weights.add(elements);
+        }
+        weights
+    }
+}
+
+// The weights at each level that are in a table, without the implicit
+// primary weights, so the next weight above one is found without going
+// through the whole table for every relation. The weights of entries that
+// are replaced by a rule stay in it, which can only leave less room between
+// weights.
+#[derive(Default)]
+struct WeightIndex([BTreeSet<u16>; 3]);
+
+impl WeightIndex {
+    fn add(&mut self, elements: &[CollationElement]) {
+        for (i, elem) in elements.iter().enumerate() {
+            for level in 1..=3 {
+                if !(level == 1 && is_implicit(elements, i)) {
+                    self.0[level as usize - 1].insert(weight(elem, level));
+                }
+            }
+        }
+    }
+
+    // The lowest weight at `level` that is higher than `above`
+    fn next(&self, level: u8, above: u16) -> u16 {
+        let default = match level {
+            1 => FIRST_IMPLICIT_PRIMARY,
+            3 => MAX_TERTIARY + 1,
+            _ => u16::MAX,
+        };
+        match above.checked_add(1) {
+            Some(from) => self.0[level as usize - 1]
+                .range(from..)
+                .next()
+                .map_or(default, |&w| w.min(default)),
+            None => default,
+        }
+    }
+
+    // Moves the weights like `make_room`
+    fn make_room(&mut self, level: u8, weight: u16, by: u16) {
+        let weights = &mut self.0[level as usize - 1];
+        if let Some(from) = weight.checked_add(1) {
+            let moved = weights.split_off(&from);
+            weights.extend(moved.into_iter().map(|w| w.saturating_add(by)));
+        }
+    }
+}
+
+/// Returns a weight strictly between `a` and `b` that leaves room for `left - 1`
+/// more weights after it, or `None` if there is no room for all of them. The
+/// weights are spread evenly, so that later rules can still insert weights
+/// on either side.
+fn weight_between(a: u16, b: u16, left: u16) -> Option<u16> {
+    if b > a && b - a > left {
+        Some(a + (b - a) / (left + 1))
+    } else {
+        None
+    }
+}
+
+// The number of relations at each level from each rule up to the next reset,
+// counting the ones of the rule itself
+fn relations_left(rules: &[Rule]) -> Vec<[u16; 4]> {
+    let mut left = vec![[0; 4]; rules.len()];
+    let mut counts = [0u16; 4];
+    for (i, rule) in rules.iter().enumerate().rev() {
+        let (level, count) = match rule {
+            Rule::SetContext { .. } | Rule::SetPosition { .. } => {
+                counts = [0; 4];
+                (0, 0)
+            }
+            Rule::Increment { level, .. } => (*level, 1),
+            Rule::MultiIncrement {
+                level,
+                multisequence,
+            } => (*level, chars(multisequence).count()),
+            Rule::Equal { .. } | Rule::MultiEqual { .. } => (0, 0),
+        };
+        if let Some(n) = level
+            .checked_sub(1)
+            .and_then(|l| counts.get_mut(l as usize))
+        {
+            *n = n.saturating_add(count as u16);
+        }
+        left[i] = counts;
+    }
+    left
+}
+
+// The number of relations at `level` that are left, see `relations_left`
+fn left_at(left: &[u16; 4], level: u8) -> u16 {
+    level
+        .checked_sub(1)
+        .and_then(|l| left.get(l as usize))
+        .map_or(1, |&n| n.max(1))
+}
+
+fn weight(elem: &CollationElement, level: u8) -> u16 {
+    match level {
+        1 => elem.primary,
+        2 => elem.secondary,
+        _ => elem.tertiary,
+    }
+}
+
+fn weight_mut(elem: &mut CollationElement, level: u8) -> &mut u16 {
+    match level {
+        1 => &mut elem.primary,
+        2 => &mut elem.secondary,
+        _ => &mut elem.tertiary,
+    }
+}
+
+// Moves the weights above `weight` at `level` up by `by`, so that the weights
+// up to `weight + by` are free
+fn make_room(elements: &mut [CollationElement], level: u8, weight: u16, by: u16) {
+    for i in 0..elements.len() {
+        if level == 1 && is_implicit(elements, i) {
+            continue;
+        }
+        let w = weight_mut(&mut elements[i], level);
+        if *w > weight {
+            *w += by;
+        }
+    }
+}
+
+// Implicit weights of unlisted characters are computed from their code points,
+// so those and the elements continuing them are never moved
+fn is_implicit(elements: &[CollationElement], i: usize) -> bool {
+    elements[i].primary >= FIRST_IMPLICIT_PRIMARY
+        || i > 0 && (FIRST_IMPLICIT_PRIMARY..=0xFBFF).contains(&elements[i - 1].primary)
+}
+
+// Turns an anchor into one sorting directly before it at the level of a
+// `[before n]` reset. The elements after the last one with a weight at that
+// level, like the accent of `é` for `[before 1]`, are dropped.
+fn step_back(anchor: &mut Vec<CollationElement>, before: Option<u8>) -> Result<(), TailoringError> {
+    let level = match before {
+        Some(level @ 1..=3) => level,
+        Some(level) => return Err(TailoringError::InvalidBeforeLevel(level)),
+        None => return Ok(()),
+    };
+    if let Some(i) = anchor.iter_mut().rposition(|e| *weight_mut(e, level) != 0) {
+        anchor.truncate(i + 1);
+        *weight_mut(&mut anchor[i], level) -= 1;
+    }
+    Ok(())
+}
+
+fn chars(multisequence: &[SequenceElement]) -> impl Iterator<Item = char> + '_ {
+    multisequence.iter().flat_map(|elem| match elem {
+        SequenceElement::Char(c) => *c..=*c,
+        SequenceElement::Range(range) => range.clone(),
+    })
+}
+
+// The tests use the bundled DUCET
+#[cfg(all(test, feature = "bundled-ducet"))]
+mod tests {
+    use super::*;
+    use crate::{collation_rules, Collator, Level};
+    use std::cmp::Ordering;
+
+    fn tailored(rules: &str) -> CollationElementTable {
+        let mut table = CollationElementTable::default();
+        table
+            .apply_rules(&collation_rules::cldr(rules).unwrap())
+            .unwrap();
+        table
+    }
+
+    fn compare(table: &CollationElementTable, a: &str, b: &str) -> (Ordering, Option<Level>) {
+        table
+            .generate_sort_key(a)
+            .compare_detailed(&table.generate_sort_key(b))
+    }
+
+    #[test]
+    fn errors() {
+        let apply = |rules: &str| {
+            CollationElementTable::default().apply_rules(&collation_rules::cldr(rules).unwrap())
+        };
+        assert_eq!(
+            apply("&\u{e000} < x"),
+            Err(TailoringError::AnchorNotFound("\u{e000}".to_owned()))
+        );
+        assert_eq!(
+            apply("&a < x / \u{e000}"),
+            Err(TailoringError::ExpansionTargetMissing(
+                "\u{e000}".to_owned()
+            ))
+        );
+
+        // The parser only accepts levels 1 to 3
+        let rules = CollationRules {
+            settings: Vec::new(),
+            rules: vec![Rule::SetContext {
+                before: Some(4),
+                sequence: "a".to_owned(),
+            }],
+        };
+        assert_eq!(
+            CollationElementTable::default().apply_rules(&rules),
+            Err(TailoringError::InvalidBeforeLevel(4))
+        );
+
+        assert!(matches!(
+            Collator::with_tailoring(CollationElementTable::default(), "&a <"),
+            Err(TailoringError::Syntax)
+        ));
+    }
+
+    #[test]
+    fn weights_between() {
+        assert_eq!(weight_between(1, 5, 1), Some(3));
+        assert_eq!(weight_between(1, 3, 1), Some(2));
+        assert_eq!(weight_between(1, 2, 1), None);
+        assert_eq!(weight_between(2, 2, 1), None);
+        assert_eq!(weight_between(3, 2, 1), None);
+        // Room for the weights of the relations that follow
+        assert_eq!(weight_between(1, 10, 2), Some(4));
+        assert_eq!(weight_between(1, 4, 3), None);
+        assert_eq!(weight_between(1, 5, 3), Some(2));
+    }
+
+    #[test]
+    fn gaps_and_rescaling() {
+        let mut table = CollationElementTable::from(
+            "0061 ; [.0010.0020.0002]\n0062 ; [.0020.0020.0002]\n0063 ; [.0021.0020.0002]\n",
+        )
+        .unwrap();
+        let rules = collation_rules::cldr("&a < x &b < y").unwrap();
+        table.apply_rules(&rules).unwrap();
+
+        // There is room between a and b, but b and c have to be moved apart
+        let primary = |s: &str| table[s][0].primary;
+        assert_eq!(primary("x"), 0x18);
+        assert_eq!(primary("b"), 0x20);
+        assert_eq!(primary("y"), 0x21);
+        assert_eq!(primary("c"), 0x22);
+    }
+
+    #[test]
+    fn dense_tailoring() {
+        let chars: Vec<char> = ('\u{e000}'..'\u{e3e8}').collect();
+        let mut rules = String::from("&a");
+        for c in &chars {
+            rules.push_str(" < ");
+            rules.push(*c);
+        }
+        let table = tailored(&rules);
+
+        // Every new character is strictly between its neighbours
+        let mut previous = table.generate_sort_key("a");
+        for c in chars.iter().chain(&['b']) {
+            let key = table.generate_sort_key(c.encode_utf8(&mut [0; 4]));
+            assert_eq!(
+                previous.compare_detailed(&key),
+                (Ordering::Less, Some(Level::Primary))
+            );
+            previous = key;
+        }
+    }
+
+    #[test]
+    fn weight_overflow() {
+        // Trailing weights can't be moved, so there is only room for the
+        // weights up to the last one
+        let mut table = CollationElementTable::default();
+        let rules = collation_rules::cldr("&[last trailing] < x < y < z").unwrap();
+        assert_eq!(
+            table.apply_rules(&rules),
+            Err(TailoringError::WeightOverflow {
+                level: Level::Primary
+            })
+        );
+
+        let mut table =
+            CollationElementTable::from("0061 ; [.0201.0020.FFFE]\n0062 ; [.0201.0020.FFFF]\n")
+                .unwrap();
+        let rules = collation_rules::cldr("&a <<< x").unwrap();
+        assert_eq!(
+            table.apply_rules(&rules),
+            Err(TailoringError::WeightOverflow {
+                level: Level::Tertiary
+            })
+        );
+    }
+
+    #[test]
+    fn increments() {
+        let table = tailored("&a < x");
+        assert_eq!(
+            compare(&table, "a", "x"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+        assert_eq!(
+            compare(&table, "\u{e1}", "x"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+        assert_eq!(
+            compare(&table, "x", "b"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+
+        let table = tailored("&a << x");
+        assert_eq!(
+            compare(&table, "a", "x"),
+            (Ordering::Less, Some(Level::Secondary))
+        );
+        assert_eq!(
+            compare(&table, "x", "b"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+
+        let table = tailored("&a <<< x");
+        assert_eq!(
+            compare(&table, "a", "x"),
+            (Ordering::Less, Some(Level::Tertiary))
+        );
+        assert_eq!(
+            compare(&table, "x", "A"),
+            (Ordering::Less, Some(Level::Tertiary))
+        );
+    }
+
+    #[test]
+    fn relation_chains() {
+        // Every relation is relative to the sequence of the one before it,
+        // not to the reset
+        let table = tailored("&a < b < c << d");
+        for (x, y, level) in [
+            ("a", "b", Level::Primary),
+            ("b", "c", Level::Primary),
+            ("c", "d", Level::Secondary),
+        ] {
+            assert_eq!(compare(&table, x, y), (Ordering::Less, Some(level)));
+        }
+        assert_eq!(
+            compare(&table, "d", "e"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+        assert_eq!(
+            table.generate_sort_key("d").primary,
+            table.generate_sort_key("c").primary
+        );
+    }
+
+    #[test]
+    fn long_relation_chains() {
+        // Room is made once for the whole chain, with the new weights spread
+        // evenly over it
+        let table =
+            CollationElementTable::from("0061 ; [.0001.0020.0002]\n0062 ; [.0002.0020.0002]\n")
+                .unwrap();
+        let chars: Vec<String> = (0xE000..0xE000 + 1000)
+            .map(|c| char::from_u32(c).unwrap().to_string())
+            .collect();
+        let rules = collation_rules::cldr(&format!("&a < {}", chars.join(" < "))).unwrap();
+        let mut tailored = table.clone();
+        tailored.apply_rules(&rules).unwrap();
+        let primary = |s: &str| tailored.generate_sort_key(s).primary;
+        assert_eq!(primary("b"), [1002]);
+        assert_eq!(primary(&chars[0]), [2]);
+        assert!(chars.windows(2).all(|w| primary(&w[0]) < primary(&w[1])));
+
+        // The relations after another reset get their own room
+        let rules = collation_rules::cldr("&a < x < y &b < z").unwrap();
+        let mut tailored = table;
+        tailored.apply_rules(&rules).unwrap();
+        let primaries: Vec<_> = ["a", "x", "y", "b", "z"]
+            .iter()
+            .map(|s| tailored.generate_sort_key(s).primary[0])
+            .collect();
+        assert_eq!(primaries[..4], [1, 2, 3, 4]);
+        assert!(primaries[4] > 4);
+    }
+
+    #[test]
+    fn implicit_anchors() {
+        // Ideographs are not in the table, but still have their place
+        let table = tailored("&\u{4e00} < x < y");
+        for (a, b) in [("\u{4e00}", "x"), ("x", "y"), ("y", "\u{4e01}")] {
+            assert_eq!(
+                compare(&table, a, b),
+                (Ordering::Less, Some(Level::Primary))
+            );
+        }
+        let table = tailored("&\u{4e00} << x &\u{fffd} < y");
+        assert_eq!(
+            compare(&table, "\u{4e00}", "x"),
+            (Ordering::Less, Some(Level::Secondary))
+        );
+        assert_eq!(
+            compare(&table, "\u{fffd}", "y"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+
+        // Unassigned code points still aren't anchors
+        let mut table = CollationElementTable::default();
+        assert_eq!(
+            table.apply_rules(&collation_rules::cldr("&\u{e000} < x").unwrap()),
+            Err(TailoringError::AnchorNotFound("\u{e000}".to_owned()))
+        );
+    }
+
+    #[test]
+    fn quoted_anchors() {
+        let table = tailored(r"&'\u0041' < x");
+        assert_eq!(
+            compare(&table, "A", "x"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+        assert_eq!(
+            compare(&table, "x", "b"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+
+        for rules in &[r"&'\'' < x", "&'' < x"] {
+            let table = tailored(rules);
+            assert_eq!(
+                compare(&table, "'", "x"),
+                (Ordering::Less, Some(Level::Primary)),
+                "{}",
+                rules
+            );
+        }
+
+        let table = tailored(r"&[before 1]'\u00E9' < x");
+        assert_eq!(
+            compare(&table, "d", "x"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+        assert_eq!(
+            compare(&table, "x", "e\u{301}"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+        assert_eq!(
+            compare(&table, "x", "e"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+    }
+
+    #[test]
+    fn before_and_equal() {
+        let table = tailored("&[before 1]b < x");
+        assert_eq!(
+            compare(&table, "a", "x"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+        assert_eq!(
+            compare(&table, "x", "b"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+
+        let table = tailored("&a = x");
+        assert_eq!(compare(&table, "a", "x"), (Ordering::Equal, None));
+    }
+
+    #[test]
+    fn equal_copies_the_anchor() {
+        let table = tailored("&a = b &a < c");
+        assert_eq!(table["b"], table["a"]);
+        assert_eq!(table.generate_sort_key("b"), table.generate_sort_key("a"));
+        assert_eq!(
+            table.generate_sort_key("xbx"),
+            table.generate_sort_key("xax")
+        );
+        assert_ne!(table.generate_sort_key("c"), table.generate_sort_key("a"));
+        assert_eq!(
+            compare(&table, "a", "c"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+        // After a reset with an expansion, the whole expansion is copied
+        let table = tailored("&\u{e6} = b");
+        assert_eq!(table["b"], table["\u{e6}"]);
+    }
+
+    #[test]
+    fn prefixes() {
+        let mut table = CollationElementTable::from(
+            "0061 ; [.0010.0020.0002]\n0062 ; [.0020.0020.0002]\n0063 ; [.0030.0020.0002]\n",
+        )
+        .unwrap();
+        table
+            .apply_rules(&collation_rules::cldr("&c <<< a|b").unwrap())
+            .unwrap();
+
+        // b only sorts like c after an a
+        let primary = |s: &str| table.generate_sort_key(s).primary;
+        assert_eq!(primary("ab"), [0x10, 0x30]);
+        assert_eq!(primary("cb"), [0x30, 0x20]);
+        assert_eq!(primary("b"), [0x20]);
+        assert_eq!(
+            compare(&table, "ac", "ab"),
+            (Ordering::Less, Some(Level::Tertiary))
+        );
+
+        // Equal relations can have a prefix too, like in the Japanese rules
+        table
+            .apply_rules(&collation_rules::cldr("&a = c|b").unwrap())
+            .unwrap();
+        let primary = |s: &str| table.generate_sort_key(s).primary;
+        assert_eq!(primary("cb"), [0x30, 0x10]);
+        assert_eq!(primary("b"), [0x20]);
+    }
+
+    #[test]
+    fn positions() {
+        let table = tailored("&[last regular] < x");
+        let x = table.generate_sort_key("x");
+
+        // After all regular characters, but before implicit and trailing
+        // weights
+        for (key, elements) in table.iter() {
+            let has_x = key.nfd().any(|c| c == 'x');
+            if !has_x && elements.iter().all(|e| !e.variable && e.primary < 0xFB00) {
+                assert!(table.generate_sort_key(key) < x, "{:?}", key);
+            }
+        }
+        assert!(x < table.generate_sort_key("\u{4e00}"));
+        assert!(x < table.generate_sort_key("\u{fffd}"));
+
+        let mut table = CollationElementTable::from(
+            "0020 ; [*0001.0020.0002]\n\
+             002D ; [*0002.0020.0002]\n\
+             0061 ; [.0010.0020.0002]\n\
+             0062 ; [.0020.0020.0002]\n\
+             0301 ; [.0000.0024.0002]\n",
+        )
+        .unwrap();
+        let rules = "&[last variable] < y &[first regular] = z &[first trailing] = w";
+        table
+            .apply_rules(&collation_rules::cldr(rules).unwrap())
+            .unwrap();
+        let primary = |s: &str| table[s][0].primary;
+        assert!(primary("-") < primary("y") && primary("y") < primary("a"));
+        assert_eq!(primary("z"), primary("a"));
+        assert_eq!(primary("w"), 0xFFFD);
+    }
+
+    #[test]
+    fn variable_positions() {
+        let table = tailored("&[last variable] < x &[before 1][first variable] < y");
+        let variable = table
+            .iter()
+            .filter(|&(key, _)| !["x", "y"].contains(&key))
+            .flat_map(|(_, elements)| elements.iter())
+            .filter(|e| e.variable && e.primary != 0)
+            .map(|e| e.primary)
+            .collect::<Vec<_>>();
+        let first = *variable.iter().min().unwrap();
+        let last = *variable.iter().max().unwrap();
+
+        // Just above the variable range, before the first regular character.
+        // Like the anchor, the tailored characters are variable.
+        let (x, y) = (&table["x"][0], &table["y"][0]);
+        assert_eq!(x.primary, last + 1);
+        assert!(x.variable);
+        assert!(table.generate_sort_key("x") < table.generate_sort_key("0"));
+        assert!(table.generate_sort_key("x") < table.generate_sort_key("a"));
+        assert!(y.primary < first && y.primary != 0);
+        assert!(y.variable);
+    }
+
+    #[test]
+    fn multi_increment_and_normalization() {
+        let table = tailored("&z <* \u{e4}\u{f6}\u{fc}");
+        assert_eq!(
+            compare(&table, "z", "a\u{308}"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+        assert_eq!(
+            compare(&table, "\u{e4}", "\u{f6}"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+        assert_eq!(
+            compare(&table, "\u{f6}", "\u{fc}"),
+            (Ordering::Less, Some(Level::Primary))
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use crate::CollationElementTable;
+use unic_ucd_normal::CanonicalCombiningClass;
+
+/// A violation of one of the well-formedness conditions of UTS #10, with the
+/// key of the offending entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WellFormednessViolation {
+    /// WF1: an element has a zero secondary but a non-zero tertiary weight.
+    IgnorableSecondary(String),
+    /// WF2: a secondary element has a secondary weight that is not higher
+    /// than the secondary weights of all primary elements.
+    LowSecondary(String),
+    /// WF2: a tertiary element has a tertiary weight that is not higher than
+    /// the tertiary weights of all primary and secondary elements.
+    LowTertiary(String),
+    /// WF3: a variable element has a zero primary weight.
+    IgnorableVariable(String),
+    /// WF4: a non-variable element has a primary weight between those of
+    /// variable elements.
+    NonVariableInVariableRange(String),
+    /// WF5: a contraction of more than two characters ends in a non-starter,
+    /// but the contraction without its last character is not in the table.
+    MissingContractionPrefix(String),
+}
+
+impl CollationElementTable {
+    /// Checks the well-formedness conditions WF1 to WF5 of UTS #10 for all
+    /// entries, which catches broken custom tables early.
+    pub fn check_well_formed(&self) -> Result<(), Vec<WellFormednessViolation>> {
+        let elements = || {
+            self.data
+                .iter()
+                .flat_map(|(k, v)| v.iter().map(move |e| (k, e)))
+        };
+
+        let max_primary_secondary = elements()
+            .filter(|(_, e)| e.primary != 0)
+            .map(|(_, e)| e.secondary)
+            .max()
+            .unwrap_or(0);
+        let max_secondary_tertiary = elements()
+            .filter(|(_, e)| e.primary != 0 || e.secondary != 0)
+            .map(|(_, e)| e.tertiary)
+            .max()
+            .unwrap_or(0);
+        let variable_primaries = elements()
+            .filter(|(_, e)| e.variable && e.primary != 0)
+            .map(|(_, e)| e.primary);
+        let variable_range = variable_primaries
+            .clone()
+            .min()
+            .zip(variable_primaries.max());
+
+        let mut violations = Vec::new();
+        for (key, elem) in elements() {
+            let violation = if elem.secondary == 0 && elem.tertiary != 0 {
+                WellFormednessViolation::IgnorableSecondary
+            } else if elem.primary == 0
+                && elem.secondary != 0
+                && elem.secondary <= max_primary_secondary
+            {
+                WellFormednessViolation::LowSecondary
+            } else if elem.primary == 0
+                && elem.secondary == 0
+                && elem.tertiary != 0
+                && elem.tertiary <= max_secondary_tertiary
+            {
+                WellFormednessViolation::LowTertiary
+            } else if elem.variable && elem.primary == 0 {
+                WellFormednessViolation::IgnorableVariable
+            } else if !elem.variable
+                && matches!(variable_range, Some((min, max)) if min <= elem.primary && elem.primary <= max)
+            {
+                WellFormednessViolation::NonVariableInVariableRange
+            } else {
+                continue;
+            };
+            violations.push(violation(key.to_string()));
+        }
+
+        for key in self.data.keys() {
+            let mut chars = key.chars();
+            if let Some(last) = chars.next_back() {
+                let prefix = chars.as_str();
+                if prefix.chars().count() >= 2
+                    && !CanonicalCombiningClass::of(last).is_not_reordered()
+                    && !self.data.contains_key(prefix)
+                {
+                    violations.push(WellFormednessViolation::MissingContractionPrefix(
+                        key.to_string(),
+                    ));
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn violations() {
+        let table =
+            CollationElementTable::from("0061 ; [.0001.0020.0002]\n0301 ; [.0000.0020.0002]\n")
+                .unwrap();
+        assert_eq!(
+            table.check_well_formed(),
+            Err(vec![WellFormednessViolation::LowSecondary(
+                "\u{301}".to_owned()
+            )])
+        );
+
+        let table = CollationElementTable::from(
+            "002D ; [*0001.0020.0002]\n\
+             0061 ; [.0002.0020.0002]\n\
+             0062 ; [.0003.0000.0002]\n\
+             0063 ; [.0004.0020.0002]\n\
+             0064 ; [*0005.0020.0002]\n\
+             0065 ; [*0000.0000.0000]\n\
+             0061 0062 0301 ; [.0006.0020.0002]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            table.check_well_formed(),
+            Err(vec![
+                WellFormednessViolation::NonVariableInVariableRange("a".to_owned()),
+                WellFormednessViolation::IgnorableSecondary("b".to_owned()),
+                WellFormednessViolation::NonVariableInVariableRange("c".to_owned()),
+                WellFormednessViolation::IgnorableVariable("e".to_owned()),
+                WellFormednessViolation::MissingContractionPrefix("ab\u{301}".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "bundled-ducet")]
+    fn ducet_is_well_formed() {
+        // Except for the Tibetan contractions with U+0F71, which lack a prefix
+        let violations = CollationElementTable::default()
+            .check_well_formed()
+            .err()
+            .unwrap_or_default();
+        assert!(violations.iter().all(|v| matches!(
+            v,
+            WellFormednessViolation::MissingContractionPrefix(key) if key.contains('\u{f71}')
+        )));
+    }
+}